@@ -1,491 +1,3348 @@
 use std::{
-    process::{Command, Stdio},
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    process::Child,
     sync::{mpsc, Arc, Mutex, atomic::{AtomicBool, Ordering}},
     thread,
+    time::{Duration, Instant, SystemTime},
 };
-use std::io::{BufRead, BufReader};
 use std::sync::mpsc::{Sender, Receiver};
-use confy;
 use eframe::egui;
 
-use crate::utils;
-use crate::types::compression::{Encoder, Resolution, Preset};
-use crate::types::app::{AppConfig, FileStatus, QueueItem};
+use crate::{encode, instance, utils};
+use crate::types::compression::{BitDepth, BitrateMode, ColorRange, ContentType, Crop, Encoder, H264_LEVELS, ImageSequence, LogLevel, QualityEstimate, Resolution, Preset, Profile, ScaleAlgorithm, ServiceTarget, SizeUnit, SourcePart, SubtitleSource, Trim};
+use crate::types::app::{AppConfig, FileStatus, MediaInfo, QueueItem, SavedQueueItem};
 
-const PROGRAM_CONFIG_NAME: &str = "video_compressor_gui";
+pub const PROGRAM_CONFIG_NAME: &str = "video_compressor_gui";
+// queue column widths, shared by the header and the rows so they line up
+const STATUS_COLUMN_WIDTH: f32 = 40.0;
+const NAME_COLUMN_WIDTH: f32 = 220.0;
+const FORMAT_COLUMN_WIDTH: f32 = 110.0;
+const SIZE_COLUMN_WIDTH: f32 = 70.0;
+const EXACT_SIZE_COLUMN_WIDTH: f32 = 110.0;
+const OUTPUT_COLUMN_WIDTH: f32 = 180.0;
+
+// how much each finished encode moves the size correction
+const SIZE_CORRECTION_WEIGHT: f64 = 0.2;
+
+const EXIT_CLEANUP_TIMEOUT: Duration = Duration::from_secs(5);
+const TIMELINE_THUMBNAILS: usize = 6;
+const RECENT_FILES_LIMIT: usize = 15;
+// offered by the file picker, anything else ffmpeg reads can still be dropped
+const VIDEO_EXTENSIONS: [&str; 14] = ["mp4", "mkv", "mov", "m4v", "avi", "webm", "wmv", "flv", "ts", "mts", "m2ts", "3gp", "mpg", "mpeg"];
+// time to call off a shutdown after the batch ends
+const SHUTDOWN_COUNTDOWN: Duration = Duration::from_secs(60);
+
+const RELEASES_API_URL: &str = "https://api.github.com/repos/wahlp/video-compressor-gui/releases/latest";
+
+// an action that waits for the user to confirm it in a modal, see MyApp::confirmation_ui
+pub enum Confirmation {
+    // settings that will change, as "name: current → default"
+    ResetDefaults(Vec<String>),
+    DeleteLeftover(u64, PathBuf),
+}
+
+impl Confirmation {
+    fn confirm_label(&self) -> &'static str {
+        match self {
+            Confirmation::ResetDefaults(_) => "Reset",
+            Confirmation::DeleteLeftover(..) => "Delete",
+        }
+    }
+}
 
 pub enum Tab {
     Main,
     Options,
+    Summary,
     Output,
 }
 
+#[derive(PartialEq, Clone, Copy)]
+pub enum QueueSortColumn {
+    Status,
+    Filename,
+    InputSize,
+    OutputSize,
+}
+
+#[derive(PartialEq)]
+pub enum CalculatorUnknown {
+    Size,
+    Duration,
+    Bitrate,
+}
+
+// progress and log lines from a running encode, held back so they reach the window a few times a second
+// instead of once per ffmpeg line
+#[derive(Default)]
+struct PendingUpdates {
+    progress: Option<f32>,
+    speed: Option<f32>,
+    lines: Vec<String>,
+}
+
+impl PendingUpdates {
+    // false for the status tags that have to be applied in order, like [done] or [stage]:
+    fn push(&mut self, line: &str) -> bool {
+        if let Some(progress_str) = line.strip_prefix("[progress]:") {
+            self.progress = progress_str.parse().ok().or(self.progress);
+        } else if let Some(speed_str) = line.strip_prefix("[speed]:") {
+            self.speed = speed_str.parse().ok().or(self.speed);
+        } else if is_status_tag(line) {
+            return false;
+        } else {
+            self.lines.push(line.to_string());
+        }
+        true
+    }
+}
+
+// tags are a plain lowercase word in brackets, ffmpeg's own lines start with e.g. "[libx264 @ 0x...]"
+fn is_status_tag(line: &str) -> bool {
+    line.strip_prefix('[')
+        .and_then(|rest| rest.split_once(']'))
+        .is_some_and(|(tag, _)| !tag.is_empty() && tag.chars().all(|c| c == '_' || c.is_ascii_lowercase()))
+}
+
+// scratch values for the size/duration/bitrate calculator
+pub struct SizeCalculator {
+    size_mb: f64,
+    duration_secs: f64,
+    bitrate_kbps: f64,
+    solve_for: CalculatorUnknown,
+}
+
 pub struct MyApp {
     config: AppConfig,
     config_dirty: bool,
+    config_saved_at: Option<Instant>,
+    config_save_error: Option<String>,
     video_queue: Arc<Mutex<Vec<QueueItem>>>,
     ffmpeg_log: Arc<Mutex<Vec<String>>>,
+    // the same lines split up by queue item, kept out of QueueItem since the queue is cloned every frame
+    item_logs: Arc<Mutex<HashMap<u64, Vec<String>>>>,
+    log_window_item: Option<u64>,
     ffmpeg_busy: Arc<AtomicBool>,
     should_start_next: Arc<Mutex<bool>>,
+    stop_after_current: Arc<AtomicBool>,
+    // only for this session, a shutdown left on by a saved setting would be a nasty surprise
+    shutdown_when_done: bool,
+    shutdown_at: Option<Instant>,
+    // what the last resolution fitting changed, shown until dismissed
+    fit_report: Option<Vec<String>>,
+    current_child: Arc<Mutex<Option<Child>>>,
+    cancel_requested: Arc<AtomicBool>,
     current_tab: Tab,
+    calculator: SizeCalculator,
+    settings_io_message: Option<String>,
+    pending_confirmation: Option<Confirmation>,
+    next_item_id: u64,
+    selected_items: HashSet<u64>,
+    // (tag, release page) of a newer release, if one was found
+    available_update: Arc<Mutex<Option<(String, String)>>>,
+    update_banner_dismissed: bool,
+    nvenc_recheck_at: Option<Instant>,
+    // last session count from nvidia-smi, which runs on its own thread as it can take a while
+    nvenc_sessions: Arc<Mutex<Option<u32>>>,
+    nvenc_query: Option<thread::JoinHandle<()>>,
+    source_check_at: Option<Instant>,
+    // stats the sources on its own thread, slow drives would otherwise stall the window
+    source_check: Option<thread::JoinHandle<()>>,
+    // display order of the queue as (column, descending), processing order is unaffected
+    queue_sort: Option<(QueueSortColumn, bool)>,
+    self_test_running: Arc<AtomicBool>,
+    // (planned, actual) output sizes of finished jobs, folded into the config's size correction
+    size_samples: Arc<Mutex<Vec<(u64, u64)>>>,
+    // timeline thumbnails: raw frames arrive from a background thread and become textures on the ui thread
+    thumbnail_frames: Arc<Mutex<HashMap<u64, Vec<Vec<u8>>>>>,
+    thumbnails: HashMap<u64, Vec<egui::TextureHandle>>,
+    thumbnails_requested: HashSet<u64>,
+    // contact sheet of an output, generated in the background and shown in a window with where it was saved
+    contact_sheet_running: Arc<AtomicBool>,
+    contact_sheet_result: Arc<Mutex<Option<Result<encode::ContactSheet, String>>>>,
+    contact_sheet: Option<Result<(PathBuf, egui::TextureHandle), String>>,
+    self_test_message: Arc<Mutex<Option<String>>>,
+    // files from later launches of the program, None when it runs as a separate instance
+    instance_rx: Option<Receiver<Vec<PathBuf>>>,
 }
 
 impl MyApp {
-    pub fn load() -> Result<Self, confy::ConfyError> {
-        Ok(Self {
+    // initial_files are queued right away, e.g. from "open with"
+    pub fn load(initial_files: Vec<PathBuf>) -> Result<Self, confy::ConfyError> {
+        let mut app = Self {
             config: confy::load(PROGRAM_CONFIG_NAME, None)?,
             config_dirty: false,
+            config_saved_at: None,
+            config_save_error: None,
             video_queue: Arc::new(Mutex::new(Vec::new())),
             ffmpeg_log: Arc::new(Mutex::new(Vec::new())),
+            item_logs: Arc::new(Mutex::new(HashMap::new())),
+            log_window_item: None,
             ffmpeg_busy: Arc::new(AtomicBool::new(false)),
             should_start_next: Arc::new(Mutex::new(false)),
+            stop_after_current: Arc::new(AtomicBool::new(false)),
+            shutdown_when_done: false,
+            shutdown_at: None,
+            fit_report: None,
+            current_child: Arc::new(Mutex::new(None)),
+            cancel_requested: Arc::new(AtomicBool::new(false)),
             current_tab: Tab::Main,
-        })
-    }
+            calculator: SizeCalculator {
+                size_mb: 10.0,
+                duration_secs: 180.0,
+                bitrate_kbps: 0.0,
+                solve_for: CalculatorUnknown::Bitrate,
+            },
+            settings_io_message: None,
+            pending_confirmation: None,
+            next_item_id: 0,
+            selected_items: HashSet::new(),
+            available_update: Arc::new(Mutex::new(None)),
+            update_banner_dismissed: false,
+            nvenc_recheck_at: None,
+            nvenc_sessions: Arc::new(Mutex::new(None)),
+            nvenc_query: None,
+            source_check_at: None,
+            source_check: None,
+            queue_sort: None,
+            self_test_running: Arc::new(AtomicBool::new(false)),
+            size_samples: Arc::new(Mutex::new(Vec::new())),
+            thumbnail_frames: Arc::new(Mutex::new(HashMap::new())),
+            thumbnails: HashMap::new(),
+            thumbnails_requested: HashSet::new(),
+            contact_sheet_running: Arc::new(AtomicBool::new(false)),
+            contact_sheet_result: Arc::new(Mutex::new(None)),
+            contact_sheet: None,
+            self_test_message: Arc::new(Mutex::new(None)),
+            instance_rx: None,
+        };
 
-    fn reset_config_to_default(&mut self) {
-        let dark_mode = self.config.dark_mode_enabled;
+        if app.config.check_for_updates {
+            app.start_update_check();
+        }
+        if app.config.single_instance {
+            app.instance_rx = instance::listen();
+        }
+        app.restore_queue();
+        for path in initial_files {
+            app.add_path(path);
+        }
 
-        self.config = AppConfig {
-            dark_mode_enabled: dark_mode,
-            ..Default::default()
-        };
+        Ok(app)
+    }
 
-        self.config_dirty = true;
+    // unfinished items and their labels, trims and settings are kept in a file beside the config
+    fn queue_file() -> Option<PathBuf> {
+        let config_path = confy::get_configuration_file_path(PROGRAM_CONFIG_NAME, None).ok()?;
+        Some(config_path.parent()?.join("queue.json"))
     }
 
-    fn start_ffmpeg_thread(&mut self) {
-        if self.ffmpeg_busy.load(Ordering::SeqCst) {
+    fn save_queue(&self) {
+        let Some(path) = Self::queue_file() else {
             return;
+        };
+        let saved: Vec<SavedQueueItem> = self.video_queue.lock().unwrap().iter()
+            .filter(|i| !matches!(i.status, FileStatus::Done | FileStatus::AlreadyOptimal))
+            .map(|i| SavedQueueItem {
+                path: i.image_sequence.as_ref().map_or(&i.path, |s| &s.first_frame).clone(),
+                label: i.label.clone(),
+                trim: i.trim,
+                video_stream: i.video_stream,
+                content_type: i.content_type,
+                settings_override: i.settings_override.clone(),
+            })
+            .collect();
+        if let Ok(text) = serde_json::to_string_pretty(&saved) {
+            std::fs::write(path, text).ok();
         }
+    }
 
-        let queue_item_path = {
-            let mut queue = match self.video_queue.lock() {
-                Ok(q) => q,
-                Err(_) => return,
-            };
-            if let Some(item) = queue.iter_mut().find(|i| matches!(i.status, FileStatus::Waiting)) {
-                item.status = FileStatus::Processing;
-                Some(item.path.clone())
-            } else {
-                None
-            }
+    // files that are gone since the last run are skipped
+    fn restore_queue(&mut self) {
+        let Some(text) = Self::queue_file().and_then(|path| std::fs::read_to_string(path).ok()) else {
+            return;
         };
-
-        let Some(queue_item) = queue_item_path else {
+        let Ok(saved) = serde_json::from_str::<Vec<SavedQueueItem>>(&text) else {
             return;
         };
+        // reopening the queue isn't a fresh use of these files, so the recent list keeps its order
+        let recent_files = self.config.recent_files.clone();
+        for item in saved {
+            let id = self.next_item_id;
+            self.add_path(item.path);
+            if let Some(added) = self.video_queue.lock().unwrap().iter_mut().find(|i| i.id == id) {
+                added.label = item.label;
+                added.trim = item.trim;
+                added.video_stream = item.video_stream;
+                added.content_type = item.content_type;
+                added.settings_override = item.settings_override;
+            }
+        }
+        self.config.recent_files = recent_files;
+    }
 
-        self.ffmpeg_busy.store(true, Ordering::SeqCst);
-
-        let log_arc = Arc::clone(&self.ffmpeg_log);
-        let busy_flag = Arc::clone(&self.ffmpeg_busy);
-        let should_start_next_clone = Arc::clone(&self.should_start_next);
-        let video_queue_clone = Arc::clone(&self.video_queue);
-        let frame_rate_option = self.config.frame_rate;
-        let encoder = self.config.encoder.clone();
-        let (log_tx, log_rx): (Sender<String>, Receiver<String>) = mpsc::channel();
-        let queue_item_clone = queue_item.clone();
-        let target_size_mb = self.config.target_size_mb;
-        let resolution = self.config.resolution.clone();
-        let config_preset = self.config.preset.clone();
+    // query the latest github release in the background so startup isn't blocked
+    fn start_update_check(&self) {
+        let available_update = Arc::clone(&self.available_update);
 
         thread::spawn(move || {
-            let Some((duration, audio_bitrate)) = get_duration_and_audio_bitrate(queue_item.to_str().unwrap()) else {
-                log_tx.send("Failed to calculate bitrate.".to_string()).ok();
-                log_tx.send("[done]".to_string()).ok();
+            #[derive(serde::Deserialize)]
+            struct Release {
+                tag_name: String,
+                html_url: String,
+            }
+
+            let Ok(response) = ureq::get(RELEASES_API_URL)
+                .set("User-Agent", PROGRAM_CONFIG_NAME)
+                .call()
+            else {
                 return;
             };
-            let Some((video_bitrate, audio_bitrate)) = calculate_bitrate(target_size_mb, duration, audio_bitrate) else {
-                log_tx.send("Failed to calculate bitrate.".to_string()).ok();
-                log_tx.send("[done]".to_string()).ok();
+            let Ok(release) = response.into_json::<Release>() else {
                 return;
             };
 
-            // build command string
-            let b_v = format!("{}", video_bitrate);
-            let b_a = format!("{}", audio_bitrate);
-            let output_path = queue_item.with_extension("compressed.mp4");
-            let mut args = vec![
-                "-i", queue_item.to_str().unwrap(),
-                "-c:v",
-                match encoder {
-                    Encoder::CpuX264 => "libx264",
-                    Encoder::GpuNvenc => "h264_nvenc",
-                },
-                "-b:v", &b_v,
-                "-c:a", "aac",
-                "-b:a", &b_a,
-                "-y", output_path.to_str().unwrap(),
-            ];
-
-            // insert optional parameters if specified
-            let mut filters = Vec::new();
-            if let Some(fps) = frame_rate_option {
-                filters.push(format!("fps={}", fps));
-            }
-            if let Some(res) = &resolution {
-                filters.push(format!("scale=-1:{}", res.to_height()));
-            }
-            let filters_str = filters.join(",");
-            if !filters.is_empty() {
-                args.splice(2..2, ["-filter:v", &filters_str]);
-            }
-
-            if let Some(preset_str) = config_preset.as_str() {
-                args.extend(["-preset", preset_str]);
-            }
-
-            // dump command string to the log for debugging
-            let cmd_string = format!("ffmpeg {}", args.iter()
-                .map(|s| utils::shell_quote(s))
-                .collect::<Vec<_>>()
-                .join(" ")
-            );
-            log_tx.send(format!("Running command: {}", cmd_string)).ok();
-
-            // run the command
-            let mut cmd = Command::new("ffmpeg")
-                .args(args)
-                .stderr(Stdio::piped())
-                .spawn()
-                .expect("failed to run ffmpeg");
-
-            let stderr = cmd.stderr.take().unwrap();
-            let reader = BufReader::new(stderr);
-            for line in reader.lines() {
-                if let Ok(line) = line {
-                    log_tx.send(line).ok();
-                }
-            }
-            cmd.wait().ok();
-
-            // check output file size
-            if let Ok(metadata) = std::fs::metadata(&output_path) {
-                let size = metadata.len();
-                log_tx.send(format!("[output_size]:{}", size)).ok();
+            let latest = utils::parse_version(&release.tag_name);
+            let current = utils::parse_version(env!("CARGO_PKG_VERSION"));
+            if let (Some(latest), Some(current)) = (latest, current)
+                && latest > current
+                && let Ok(mut update) = available_update.lock()
+            {
+                *update = Some((release.tag_name, release.html_url));
             }
-
-            log_tx.send("[done]".to_string()).ok();
         });
+    }
 
-        thread::spawn(move || {
-            while let Ok(line) = log_rx.recv() {
-                // when job completes, update flags and file status
-                if line == "[done]" {
-                    busy_flag.store(false, Ordering::SeqCst);
-                    if let Ok(mut queue) = video_queue_clone.lock() {
-                        if let Some(item) = queue.iter_mut().find(|i| i.path == queue_item_clone) {
-                            item.status = FileStatus::Done;
-                        }
-                    }
-                    if let Ok(mut flag) = should_start_next_clone.lock() {
-                        *flag = true;
-                    }
-                } else if let Some(size_str) = line.strip_prefix("[output_size]:") {
-                    if let Ok(size) = size_str.parse::<u64>() {
-                        if let Ok(mut queue) = video_queue_clone.lock() {
-                            if let Some(item) = queue.iter_mut().find(|i| i.path == queue_item_clone) {
-                                item.output_size_bytes = Some(size);
-                            }
+    fn default_config(&self) -> AppConfig {
+        AppConfig {
+            dark_mode_enabled: self.config.dark_mode_enabled,
+            recent_files: self.config.recent_files.clone(),
+            ..Default::default()
+        }
+    }
+
+    fn reset_config_to_default(&mut self) {
+        self.config = self.default_config();
+        self.config_dirty = true;
+    }
+
+    // "name: current → default" for every setting a reset would change
+    fn reset_changes(&self) -> Vec<String> {
+        let (Ok(serde_json::Value::Object(current)), Ok(serde_json::Value::Object(defaults))) =
+            (serde_json::to_value(&self.config), serde_json::to_value(self.default_config()))
+        else {
+            return Vec::new();
+        };
+        current.iter()
+            .filter(|(key, value)| defaults.get(*key) != Some(value))
+            .map(|(key, value)| format!(
+                "{}: {} → {}",
+                key.replace('_', " "),
+                value,
+                defaults.get(key).unwrap_or(&serde_json::Value::Null),
+            ))
+            .collect()
+    }
+
+    // the pending confirmation as a modal. the action only runs when confirmed, closing it any other way drops it
+    fn confirmation_ui(&mut self, ctx: &egui::Context) {
+        let Some(confirmation) = &self.pending_confirmation else {
+            return;
+        };
+        let mut confirmed = false;
+        let mut closed = false;
+        let modal = egui::Modal::new(egui::Id::new("confirmation")).show(ctx, |ui| {
+            ui.set_max_width(420.0);
+            match confirmation {
+                Confirmation::ResetDefaults(changes) => {
+                    ui.heading("Reset to defaults?");
+                    ui.label(format!("{} settings will change:", changes.len()));
+                    egui::ScrollArea::vertical().max_height(240.0).show(ui, |ui| {
+                        for change in changes {
+                            ui.monospace(change);
                         }
-                    }
-                } else {
-                    if let Ok(mut log) = log_arc.lock() {
-                        log.push(line);
-                    }
+                    });
+                }
+                Confirmation::DeleteLeftover(_, path) => {
+                    ui.heading("Delete unfinished output?");
+                    ui.label(format!("{} will be deleted permanently.", path.display()));
                 }
             }
+            ui.separator();
+            ui.horizontal(|ui| {
+                confirmed = ui.button(confirmation.confirm_label()).clicked();
+                closed = ui.button("Cancel").clicked();
+            });
         });
-    }
-
-    fn apply_theme(&mut self, ctx: &egui::Context) {
-        if self.config.dark_mode_enabled {
-            ctx.set_theme(egui::Theme::Dark);
-        } else {
-            ctx.set_theme(egui::Theme::Light);
+        if confirmed || closed || modal.should_close() {
+            let confirmation = self.pending_confirmation.take();
+            if let Some(confirmation) = confirmation.filter(|_| confirmed) {
+                self.apply_confirmation(confirmation);
+            }
         }
     }
-}
 
-// read input video file's parameters to calculate output file's parameters later
-fn get_duration_and_audio_bitrate(path: &str) -> Option<(f64, u32)> {
-    let output = Command::new("ffprobe")
-        .args([
-            "-v", "error",
-            "-select_streams", "a:0",
-            "-show_entries", "format=duration:stream=bit_rate",
-            "-of", "default=noprint_wrappers=1:nokey=1",
-            path,
-        ])
-        .output()
-        .ok()?;
-
-    if !output.status.success() {
-        return None;
+    fn apply_confirmation(&mut self, confirmation: Confirmation) {
+        match confirmation {
+            Confirmation::ResetDefaults(_) => self.reset_config_to_default(),
+            Confirmation::DeleteLeftover(item_id, _) => self.resolve_leftover(item_id, false),
+        }
     }
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let mut lines = stdout.lines();
-    
-    let bitrate = lines.next()?.trim().parse::<u32>().ok()?;
-    let duration = lines.next()?.trim().parse::<f64>().ok()?;
-    
-    Some((duration, bitrate))
-}
-
-fn calculate_bitrate(size_upper_bound_mb: u32, duration: f64, mut audio_bitrate: u32) -> Option<(u32, u32)> {
-    // calculate the allowed bits per second to reach target output file size
-    let gib_to_gb_conversion = 1.073741824;
-    let target_total_bitrate = (size_upper_bound_mb * 1000 * 1000 * 8) as f64 / (gib_to_gb_conversion * duration);
+    fn export_settings(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("JSON", &["json"])
+            .set_file_name("video_compressor_settings.json")
+            .save_file()
+        else {
+            return;
+        };
 
-    // throttle audio bitrate if bandwidth is bad
-    if 10.0 * audio_bitrate as f64 > target_total_bitrate {
-        audio_bitrate = (target_total_bitrate / 10.0) as u32;
-        audio_bitrate = audio_bitrate.clamp(64_000, 256_000)
+        let result = serde_json::to_value(self.config.without_history())
+            .map(|mut value| {
+                if let Some(fields) = value.as_object_mut() {
+                    fields.remove("recent_files");
+                }
+                value
+            })
+            .and_then(|value| serde_json::to_string_pretty(&value))
+            .map_err(|e| e.to_string())
+            .and_then(|json| std::fs::write(&path, json).map_err(|e| e.to_string()));
+        self.settings_io_message = Some(match result {
+            Ok(()) => format!("Exported settings to {}", path.display()),
+            Err(err) => format!("Failed to export settings: {}", err),
+        });
     }
 
-    // allocate some bitrate for audio, spend the remaining bitrate on video
-    let video_bitrate = (target_total_bitrate as u32).saturating_sub(audio_bitrate);
+    // unknown fields (e.g. from a newer version) are ignored with a warning instead of failing
+    fn import_settings(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("JSON", &["json"])
+            .pick_file()
+        else {
+            return;
+        };
 
-    Some((video_bitrate, audio_bitrate))
-}
+        let imported = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| serde_json::from_str::<serde_json::Value>(&text).map_err(|e| e.to_string()))
+            .and_then(|value| {
+                let known_fields = serde_json::to_value(AppConfig::default()).map_err(|e| e.to_string())?;
+                let unknown_fields: Vec<String> = value.as_object()
+                    .ok_or("expected a JSON object")?
+                    .keys()
+                    .filter(|key| known_fields.get(key.as_str()).is_none())
+                    .cloned()
+                    .collect();
+                let config = serde_json::from_value::<AppConfig>(value).map_err(|e| e.to_string())?;
+                Ok((config, unknown_fields))
+            });
 
-impl eframe::App for MyApp {
-    fn update(&mut self, ctx: &egui::Context, _: &mut eframe::Frame) {
-        ctx.set_zoom_factor(1.2);
-        self.apply_theme(ctx);
-        
-        // Automatically start next compression job if flagged
-        if !self.ffmpeg_busy.load(Ordering::SeqCst) && !self.video_queue.lock().unwrap().is_empty() {
-            let should_start = {
-                if let Ok(mut flag) = self.should_start_next.lock() {
-                    if *flag {
-                        *flag = false;
-                        true
-                    } else {
-                        false
-                    }
+        self.settings_io_message = Some(match imported {
+            Ok((config, unknown_fields)) => {
+                // the recent list is this machine's own, whatever the file holds
+                self.config = AppConfig { recent_files: std::mem::take(&mut self.config.recent_files), ..config };
+                self.config_dirty = true;
+                if unknown_fields.is_empty() {
+                    format!("Imported settings from {}", path.display())
                 } else {
-                    false
+                    format!("Imported settings, ignored unknown fields: {}", unknown_fields.join(", "))
                 }
-            };
-
-            if should_start {
-                self.start_ffmpeg_thread();
             }
-        }
+            Err(err) => format!("Failed to import settings: {}", err),
+        });
+    }
 
-        // Draw top tab bar
-        egui::TopBottomPanel::top("tabs").show(ctx, |ui| {
-            ui.horizontal(|ui| {
-                if ui.selectable_label(matches!(self.current_tab, Tab::Main), "Main").clicked() {
-                    self.current_tab = Tab::Main;
+    fn add_to_queue(&mut self, path: PathBuf, size_bytes: u64, image_sequence: Option<ImageSequence>) {
+        // a sequence is remembered by a frame, which add_path turns back into the whole sequence
+        self.remember_recent(image_sequence.as_ref().map_or(&path, |s| &s.first_frame).clone());
+        self.video_queue.lock().unwrap().push(QueueItem {
+            id: self.next_item_id,
+            path,
+            size_bytes,
+            status: FileStatus::Waiting,
+            output_size_bytes: None,
+            progress: None,
+            speed: None,
+            stage: None,
+            media_info: None,
+            modified: None,
+            changed_on_disk: false,
+            label: String::new(),
+            image_sequence,
+            trim: None,
+            video_stream: None,
+            content_type: None,
+            started_at: None,
+            elapsed: None,
+            encoder_used: None,
+            ffmpeg_command: None,
+            eligible: true,
+            leftover_output: None,
+            settings_override: None,
+        });
+        self.probe_in_background(self.next_item_id);
+        self.next_item_id += 1;
+    }
+
+    fn remember_recent(&mut self, path: PathBuf) {
+        self.config.recent_files.retain(|p| *p != path);
+        self.config.recent_files.insert(0, path);
+        self.config.recent_files.truncate(RECENT_FILES_LIMIT);
+        self.config_dirty = true;
+    }
+
+    // files that no longer exist are left out but kept in the list, in case a drive is just unplugged
+    fn recent_files_menu(&mut self, ui: &mut egui::Ui) {
+        ui.add_enabled_ui(!self.config.recent_files.is_empty(), |ui| {
+            ui.menu_button("Recent", |ui| {
+                let existing: Vec<PathBuf> = self.config.recent_files.iter().filter(|p| p.exists()).cloned().collect();
+                if existing.is_empty() {
+                    ui.label(egui::RichText::new("None of the recent files exist anymore").weak());
                 }
-                if ui.selectable_label(matches!(self.current_tab, Tab::Options), "Options").clicked() {
-                    self.current_tab = Tab::Options;
+                for path in existing {
+                    let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+                    if ui.button(name).on_hover_text(path.display().to_string()).clicked() {
+                        self.add_path(path);
+                        ui.close_menu();
+                    }
                 }
-                if ui.selectable_label(matches!(self.current_tab, Tab::Output), "Debug Output").clicked() {
-                    self.current_tab = Tab::Output;
+                ui.separator();
+                if ui.button("Clear Recent").clicked() {
+                    self.config.recent_files.clear();
+                    self.config_dirty = true;
+                    ui.close_menu();
                 }
             });
         });
+    }
 
-        // Main panel based on current tab
-        egui::CentralPanel::default().show(ctx, |ui| {
-            match self.current_tab {
-                Tab::Main => {
-                    // Drag & drop handler
-                    for file in ctx.input(|i| i.raw.dropped_files.clone()) {
-                        if let Some(path) = file.path {
-                            if let Ok(metadata) = std::fs::metadata(&path) {
-                                let size_bytes = metadata.len();
-                                self.video_queue.lock().unwrap().push(QueueItem {
-                                    path,
-                                    size_bytes,
-                                    status: FileStatus::Waiting,
-                                    output_size_bytes: None,
-                                });
-                            }
-                        }
-                    }
+    // dropped or picked paths: a video, a text list of videos, or a folder or frame of numbered images
+    fn add_path(&mut self, path: PathBuf) {
+        if is_path_list(&path) {
+            self.import_list(&path);
+        } else if let Some(sequence) = encode::detect_image_sequence(&path, self.config.sequence_fps) {
+            self.add_image_sequence(sequence);
+        } else if path.is_dir() {
+            self.ffmpeg_log.lock().unwrap().push(format!("No numbered images found in {}.", path.display()));
+        } else if let Ok(metadata) = std::fs::metadata(&path) {
+            self.add_to_queue(path, metadata.len(), None);
+        }
+    }
 
-                    let queue = self.video_queue.lock().unwrap().clone();
-                    if queue.is_empty() {
-                        // Add space to center vertically
-                        let available_height = ui.available_height();
-                        let prompt_height = 120.0;
-                        ui.add_space((available_height - prompt_height) / 2.0);
+    fn add_image_sequence(&mut self, sequence: ImageSequence) {
+        // selecting several frames of one sequence finds the same sequence for each of them
+        let already_waiting = self.video_queue.lock().unwrap().iter()
+            .filter(|i| i.status == FileStatus::Waiting)
+            .any(|i| i.image_sequence.as_ref().is_some_and(|s| s.pattern == sequence.pattern));
+        if already_waiting {
+            return;
+        }
+        self.ffmpeg_log.lock().unwrap().push(format!(
+            "Added {} frames from {} at {} fps.",
+            sequence.frame_count, sequence.pattern.display(), sequence.fps,
+        ));
+        self.add_to_queue(sequence.pattern.clone(), sequence.size_bytes, Some(sequence));
+    }
 
-                        ui.vertical_centered(|ui| {
-                            ui.label(egui::RichText::new("📁").size(40.0));
-                            ui.label(egui::RichText::new("Drop video files here to begin").heading().weak());
-                        });
-                    } else {
-                        if self.ffmpeg_busy.load(Ordering::SeqCst) {
-                            ui.add_sized(
-                                egui::vec2(200.0, 40.0),
-                                egui::Button::new(egui::RichText::new("Compressing..."))
-                            );
-                        } else {
-                            if ui
-                                .add_sized(
-                                    egui::vec2(200.0, 40.0),
-                                    egui::Button::new(egui::RichText::new("Start Compression").strong()).wrap(),
-                                )
-                                .clicked()
-                            {
-                                self.start_ffmpeg_thread();
-                            }
-                        }
-                        ui.separator();
+    fn pick_files_to_add(&mut self) {
+        if let Some(paths) = rfd::FileDialog::new()
+            .add_filter("Video", &VIDEO_EXTENSIONS)
+            .add_filter("All files", &["*"])
+            .pick_files()
+        {
+            for path in paths {
+                self.add_path(path);
+            }
+        }
+    }
 
-                        ui.label("Queue:");
-                        egui::Grid::new("queue_grid")
-                            .striped(true)
-                            .show(ui, |ui| {
-                                ui.label(egui::RichText::new("Status").strong());
-                                ui.label(egui::RichText::new("Filename").strong());
-                                ui.label(egui::RichText::new("Input Size").strong());
-                                ui.label(egui::RichText::new("Output Size").strong());
-                                ui.end_row();
-
-                                for item in queue.iter() {
-                                    let emoji = match item.status {
-                                        FileStatus::Waiting => "🕓",
-                                        FileStatus::Processing => "🔄",
-                                        FileStatus::Done => "✅",
-                                    };
-                                    ui.label(emoji);
-                                    ui.label(item.path.file_name().unwrap_or_default().to_string_lossy());
-                                    ui.label(utils::format_size(item.size_bytes));
-                                    ui.label(
-                                        item.output_size_bytes.map(utils::format_size).unwrap_or_else(|| "-".to_string())
-                                    );
-                                    ui.end_row();
-                                }
-                            });
-                    }
+    fn pick_image_sequence(&mut self) {
+        if let Some(path) = rfd::FileDialog::new()
+            .set_title("Pick any frame of the sequence")
+            .add_filter("Images", &["png", "jpg", "jpeg", "tif", "tiff", "bmp", "webp"])
+            .pick_file()
+        {
+            self.add_path(path);
+        }
+    }
+
+    // one path per line, relative paths count from the list's folder. blank lines and
+    // #comments are skipped, so m3u playlists work too
+    fn import_list(&mut self, list_path: &Path) {
+        let text = match std::fs::read_to_string(list_path) {
+            Ok(text) => text,
+            Err(err) => {
+                self.ffmpeg_log.lock().unwrap().push(format!("Failed to read {}: {}", list_path.display(), err));
+                return;
+            }
+        };
+        let base = list_path.parent().unwrap_or(Path::new(""));
+        let (mut added, mut missing) = (0, 0);
+        for line in text.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+            let path = base.join(line.trim_matches('"'));
+            match std::fs::metadata(&path) {
+                Ok(metadata) if metadata.is_file() => {
+                    self.add_to_queue(path, metadata.len(), None);
+                    added += 1;
                 }
+                _ => missing += 1,
+            }
+        }
 
-                Tab::Options => {
-                    ui.label(egui::RichText::new("Compression").strong());
+        let mut log = self.ffmpeg_log.lock().unwrap();
+        log.push(format!("Imported {} files from {}.", added, list_path.display()));
+        if missing > 0 {
+            log.push(format!("{} listed files could not be found.", missing));
+        }
+    }
 
-                    ui.horizontal(|ui| {
-                        ui.label("Target size (MB):");
-                        if ui.add(egui::DragValue::new(&mut self.config.target_size_mb)).changed() {
-                            self.config_dirty = true;
-                        }
-                    });
+    fn pick_list_to_import(&mut self) {
+        if let Some(path) = rfd::FileDialog::new()
+            .add_filter("File list", &["txt", "m3u", "m3u8"])
+            .pick_file()
+        {
+            self.import_list(&path);
+        }
+    }
 
-                    ui.horizontal(|ui| {
-                        ui.label("Frame rate (optional):");
-                        let mut fr_string = self.config.frame_rate.map(|v| v.to_string()).unwrap_or_default();
-                        if ui.add_sized(
-                                egui::vec2(40.0, 20.0),
-                                egui::TextEdit::singleline(&mut fr_string)
-                            ).changed() 
-                        {
-                            self.config.frame_rate = fr_string.trim().parse().ok();
-                            self.config_dirty = true;
-                        }
-                    });
+    fn probe_in_background(&self, item_id: u64) {
+        let video_queue = Arc::clone(&self.video_queue);
+        let log = Arc::clone(&self.ffmpeg_log);
+        let Some((path, output_path, sequence)) = video_queue.lock().unwrap().iter().find(|i| i.id == item_id).map(|i| {
+            let config = i.settings_override.as_ref().unwrap_or(&self.config);
+            (i.path.clone(), encode::output_path_for(&i.path, config), i.image_sequence.clone())
+        }) else {
+            return;
+        };
 
-                    ui.horizontal(|ui| {
-                        ui.label("Encoder:");
-                        ui.selectable_value(&mut self.config.encoder, Encoder::CpuX264, "CPU")
-                            .changed().then(|| {
-                                self.config_dirty = true;
-                            });
-                        ui.selectable_value(&mut self.config.encoder, Encoder::GpuNvenc, "GPU")
-                            .on_hover_ui(|ui| {
-                                ui.label("Faster than CPU, but produces larger file size");
-                            }).changed().then(|| {
-                                self.config_dirty = true;
-                            });
+        // a sequence has no single file to probe, its frames supply the picture and the count its duration
+        if let Some(sequence) = sequence {
+            thread::spawn(move || {
+                let frame_info = encode::probe_media_info(&sequence.first_frame).unwrap_or_default();
+                if let Ok(mut queue) = video_queue.lock()
+                    && let Some(item) = queue.iter_mut().find(|i| i.id == item_id)
+                {
+                    item.media_info = Some(MediaInfo {
+                        container: "image sequence".to_string(),
+                        audio_codec: None,
+                        frame_rate: Some(sequence.fps as f64),
+                        duration: Some(sequence.duration()),
+                        ..frame_info
                     });
+                }
+            });
+            return;
+        }
 
-                    ui.horizontal(|ui| {
-                        ui.label("Resolution:");
-                        let current = &mut self.config.resolution;
+        thread::spawn(move || {
+            if let Ok(metadata) = std::fs::metadata(&path)
+                && let Ok(mut queue) = video_queue.lock()
+                && let Some(item) = queue.iter_mut().find(|i| i.id == item_id)
+            {
+                item.size_bytes = metadata.len();
+                item.modified = metadata.modified().ok();
+                item.changed_on_disk = false;
+            }
 
-                        let options = [
-                            (None, "Original"),
-                            (Some(Resolution::R1080), "1080p"),
-                            (Some(Resolution::R720), "720p"),
-                            (Some(Resolution::R480), "480p"),
-                        ];
+            if let Ok(media_info) = encode::probe_media_info(&path)
+                && let Ok(mut queue) = video_queue.lock()
+                && let Some(item) = queue.iter_mut().find(|i| i.id == item_id)
+            {
+                item.media_info = Some(media_info);
+            }
 
-                        for (val, label) in options {
-                            if ui
-                                .selectable_label(current == &val, label)
-                                .clicked()
-                            {
-                                *current = val.clone();
-                                self.config_dirty = true;
+            // an interrupted encode leaves a .part file, or a cut-off output when writing in place
+            let partial_path = encode::partial_path(&output_path);
+            let output_path = if partial_path.exists() { partial_path } else { output_path };
+            if (output_path.extension().is_some_and(|e| e == "part") || encode::is_incomplete_output(&output_path))
+                && let Ok(mut queue) = video_queue.lock()
+                && let Some(item) = queue.iter_mut().find(|i| i.id == item_id)
+            {
+                if let Ok(mut log) = log.lock() {
+                    log.push(format!(
+                        "Found an unfinished output from an earlier run: {}. It will be overwritten unless you keep it.",
+                        output_path.display(),
+                    ));
+                }
+                item.leftover_output = Some(output_path);
+            }
+        });
+    }
+
+    // refresh a row after its source was replaced, thumbnails are regenerated on the next expand
+    fn reprobe(&mut self, item_id: u64) {
+        if let Ok(mut queue) = self.video_queue.lock()
+            && let Some(item) = queue.iter_mut().find(|i| i.id == item_id)
+        {
+            item.media_info = None;
+        }
+        self.thumbnails.remove(&item_id);
+        self.thumbnails_requested.remove(&item_id);
+        self.probe_in_background(item_id);
+    }
+
+    // flags rows whose source file was modified after it was probed, checked every few seconds
+    fn check_for_source_changes(&mut self) {
+        if self.source_check_at.is_some_and(|at| Instant::now() < at)
+            || self.source_check.as_ref().is_some_and(|check| !check.is_finished())
+        {
+            return;
+        }
+        self.source_check_at = Some(Instant::now() + Duration::from_secs(2));
+
+        let to_check: Vec<(u64, PathBuf, SystemTime, u64)> = {
+            let Ok(queue) = self.video_queue.lock() else {
+                return;
+            };
+            queue.iter()
+                .filter(|i| i.status != FileStatus::Processing && !i.changed_on_disk)
+                .filter_map(|i| Some((i.id, i.path.clone(), i.modified?, i.size_bytes)))
+                .collect()
+        };
+        let video_queue = Arc::clone(&self.video_queue);
+        self.source_check = Some(thread::spawn(move || {
+            let changed: Vec<(u64, SystemTime)> = to_check.into_iter()
+                .filter_map(|(id, path, modified, size_bytes)| {
+                    let metadata = std::fs::metadata(path).ok()?;
+                    (metadata.modified().ok() != Some(modified) || metadata.len() != size_bytes).then_some((id, modified))
+                })
+                .collect();
+            let Ok(mut queue) = video_queue.lock() else {
+                return;
+            };
+            // an item probed again while its file was being looked at already has the new state
+            for (id, modified) in changed {
+                if let Some(item) = queue.iter_mut().find(|i| i.id == id && i.modified == Some(modified)) {
+                    item.changed_on_disk = true;
+                }
+            }
+        }));
+    }
+
+    // a leftover can't be resumed, so either delete it or move it aside where the next encode won't overwrite it
+    fn resolve_leftover(&mut self, item_id: u64, keep: bool) {
+        let Ok(mut queue) = self.video_queue.lock() else {
+            return;
+        };
+        let Some(leftover) = queue.iter_mut().find(|i| i.id == item_id).and_then(|i| i.leftover_output.take()) else {
+            return;
+        };
+        let result = if keep {
+            // a .part file is named after the output it was going to become
+            let named = if leftover.extension().is_some_and(|e| e == "part") { leftover.with_extension("") } else { leftover.clone() };
+            let extension = named.extension().unwrap_or_default().to_string_lossy();
+            std::fs::rename(&leftover, named.with_extension(format!("partial.{}", extension)))
+        } else {
+            std::fs::remove_file(&leftover)
+        };
+        if let Err(err) = result
+            && let Ok(mut log) = self.ffmpeg_log.lock()
+        {
+            log.push(format!("Failed to clean up {}: {}", leftover.display(), err));
+        }
+    }
+
+    // frame size of the first selected probed file, or of the first probed one in the queue
+    fn probed_frame_size(&self) -> (u32, u32) {
+        let queue = self.video_queue.lock().unwrap();
+        let size = |item: &QueueItem| {
+            let info = item.media_info.as_ref()?;
+            let stream = item.video_stream.and_then(|s| info.video_streams.iter().find(|v| v.index == s));
+            match stream {
+                Some(stream) => Some((stream.width?, stream.height?)),
+                None => Some((info.width?, info.height?)),
+            }
+        };
+        queue.iter().filter(|i| self.selected_items.contains(&i.id)).find_map(size)
+            .or_else(|| queue.iter().find_map(size))
+            .unwrap_or((1920, 1080))
+    }
+
+    fn remove_selected(&mut self) {
+        if let Ok(mut queue) = self.video_queue.lock() {
+            queue.retain(|i| i.status == FileStatus::Processing || !self.selected_items.contains(&i.id));
+        }
+        self.selected_items.clear();
+    }
+
+    fn retry_selected(&mut self) {
+        if let Ok(mut queue) = self.video_queue.lock() {
+            for item in queue.iter_mut().filter(|i| self.selected_items.contains(&i.id)) {
+                if matches!(item.status, FileStatus::Done | FileStatus::AlreadyOptimal | FileStatus::Failed(_)) {
+                    item.status = FileStatus::Waiting;
+                    item.eligible = true;
+                    item.output_size_bytes = None;
+                }
+            }
+        }
+    }
+
+    fn apply_settings_to_selected(&mut self) {
+        if let Ok(mut queue) = self.video_queue.lock() {
+            for item in queue.iter_mut().filter(|i| self.selected_items.contains(&i.id)) {
+                if item.status != FileStatus::Processing {
+                    item.settings_override = Some(self.config.without_history());
+                }
+            }
+        }
+    }
+
+    // waiting files whose settings leave them too few bits per pixel to look acceptable. files left out
+    // of the current run only count when asked for
+    fn poor_quality_items(&self, include_ineligible: bool) -> Vec<(u64, String)> {
+        let queue = self.video_queue.lock().unwrap();
+        queue.iter()
+            .filter(|i| i.status == FileStatus::Waiting && (i.eligible || include_ineligible))
+            .filter(|i| {
+                let config = i.settings_override.as_ref().unwrap_or(&self.config);
+                config.quality_target_vmaf.is_none()
+                    && i.media_info.as_ref()
+                        .and_then(|info| encode::estimate_quality(config, info, i.trim))
+                        .is_some_and(|(_, estimate)| estimate == QualityEstimate::Poor)
+            })
+            .map(|i| (i.id, i.path.file_name().unwrap_or_default().to_string_lossy().into_owned()))
+            .collect()
+    }
+
+    // gives each item its own settings with the resolution lowered until the estimate is at least fair,
+    // and reports what changed
+    fn lower_resolution_for(&mut self, item_ids: &[u64]) {
+        let mut report = Vec::new();
+        {
+            let mut queue = self.video_queue.lock().unwrap();
+            for item in queue.iter_mut().filter(|i| item_ids.contains(&i.id) && i.status == FileStatus::Waiting) {
+                let name = item.path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+                let config = item.settings_override.as_ref().unwrap_or(&self.config);
+                let Some(info) = &item.media_info else {
+                    continue;
+                };
+                let Some(resolution) = encode::resolution_for_quality(config, info, item.trim) else {
+                    report.push(format!("{}: already 480p or smaller, left as is", name));
+                    continue;
+                };
+                let lowered = AppConfig { resolution: Some(resolution.clone()), ..config.without_history() };
+                let estimate = encode::estimate_quality(&lowered, info, item.trim).map_or("unknown", |(_, e)| e.label());
+                report.push(format!("{}: lowered to {}, {} quality", name, resolution, estimate.to_lowercase()));
+                item.settings_override = Some(lowered);
+            }
+        }
+        if let Ok(mut log) = self.ffmpeg_log.lock() {
+            log.extend(report.iter().map(|line| format!("Fit to target: {}", line)));
+        }
+        self.fit_report = Some(report);
+    }
+
+    fn fit_all_to_target(&mut self) {
+        let item_ids: Vec<u64> = self.poor_quality_items(true).into_iter().map(|(id, _)| id).collect();
+        if item_ids.is_empty() {
+            self.fit_report = Some(vec!["Every waiting file already looks fair or better at its target.".to_string()]);
+            return;
+        }
+        self.lower_resolution_for(&item_ids);
+    }
+
+    // requeue a finished item, optionally swapping its source for the previous output
+    fn compress_again(&mut self, item_id: u64, from_output: bool) {
+        if let Ok(mut queue) = self.video_queue.lock()
+            && let Some(item) = queue.iter_mut().find(|i| i.id == item_id)
+        {
+            if from_output {
+                let config = item.settings_override.as_ref().unwrap_or(&self.config);
+                let source = item.image_sequence.as_ref().map_or(item.path.clone(), |s| s.name_path());
+                let output_path = encode::output_path_for(&source, config);
+                let Ok(metadata) = std::fs::metadata(&output_path) else {
+                    return;
+                };
+                item.path = output_path;
+                item.size_bytes = metadata.len();
+                item.media_info = None;
+                item.image_sequence = None;
+                item.video_stream = None;
+            }
+            item.status = FileStatus::Waiting;
+            item.eligible = true;
+            item.output_size_bytes = None;
+        }
+        if from_output {
+            self.probe_in_background(item_id);
+        }
+    }
+
+    fn move_to_front(&mut self, item_id: u64) {
+        if let Ok(mut queue) = self.video_queue.lock()
+            && let Some(index) = queue.iter().position(|i| i.id == item_id)
+        {
+            let item = queue.remove(index);
+            queue.insert(0, item);
+        }
+    }
+
+    // the waiting file the queue picks up next, from the end when newer files go first
+    fn next_waiting_index(&self, queue: &[QueueItem]) -> Option<usize> {
+        let is_next = |i: &QueueItem| i.status == FileStatus::Waiting && i.eligible;
+        if self.config.newest_first {
+            queue.iter().rposition(is_next)
+        } else {
+            queue.iter().position(is_next)
+        }
+    }
+
+    // marks which waiting files the run picks up, then starts it
+    fn start_run(&mut self, selected_only: bool) {
+        if let Ok(mut queue) = self.video_queue.lock() {
+            for item in queue.iter_mut().filter(|i| i.status == FileStatus::Waiting) {
+                item.eligible = !selected_only || self.selected_items.contains(&item.id);
+            }
+        }
+        self.start_ffmpeg_thread();
+    }
+
+    // kill the running ffmpeg, the worker then returns its item to the queue
+    fn cancel_current_job(&mut self) {
+        self.cancel_requested.store(true, Ordering::SeqCst);
+        if let Ok(mut child) = self.current_child.lock()
+            && let Some(child) = child.as_mut()
+        {
+            child.kill().ok();
+        }
+    }
+
+    // jump an item ahead of the queue, interrupting the running job if asked to
+    fn prioritize(&mut self, item_id: u64, interrupt: bool) {
+        if self.config.newest_first {
+            if let Ok(mut queue) = self.video_queue.lock()
+                && let Some(index) = queue.iter().position(|i| i.id == item_id)
+            {
+                let item = queue.remove(index);
+                queue.push(item);
+            }
+        } else {
+            self.move_to_front(item_id);
+        }
+        if let Ok(mut queue) = self.video_queue.lock()
+            && let Some(item) = queue.iter_mut().find(|i| i.id == item_id)
+        {
+            item.eligible = true;
+        }
+        if !self.ffmpeg_busy.load(Ordering::SeqCst) {
+            if interrupt {
+                self.start_ffmpeg_thread();
+            }
+        } else if interrupt {
+            self.cancel_current_job();
+        }
+    }
+
+    // polls nvidia-smi at most every few seconds while the queue is held at the session limit. the
+    // count is fetched in the background, the queue is held until it's in and retried from update()
+    fn nvenc_session_available(&mut self, session_limit: u32) -> bool {
+        if self.nvenc_query.as_ref().is_some_and(|query| !query.is_finished()) {
+            return false;
+        }
+        if self.nvenc_query.take().is_none() {
+            if self.nvenc_recheck_at.is_some_and(|at| Instant::now() < at) {
+                return false;
+            }
+            let sessions = self.nvenc_sessions.clone();
+            self.nvenc_query = Some(thread::spawn(move || {
+                *sessions.lock().unwrap() = encode::nvenc_sessions_in_use();
+            }));
+            return false;
+        }
+
+        let Some(in_use) = *self.nvenc_sessions.lock().unwrap() else {
+            return true;
+        };
+        if in_use < session_limit {
+            self.nvenc_recheck_at = None;
+            return true;
+        }
+
+        if self.nvenc_recheck_at.is_none()
+            && let Ok(mut log) = self.ffmpeg_log.lock()
+        {
+            log.push(format!(
+                "NVENC session limit reached ({} of {} in use), holding the queue until one is free.",
+                in_use, session_limit,
+            ));
+        }
+        self.nvenc_recheck_at = Some(Instant::now() + Duration::from_secs(5));
+        false
+    }
+
+    fn start_ffmpeg_thread(&mut self) {
+        if self.ffmpeg_busy.load(Ordering::SeqCst) {
+            return;
+        }
+
+        let next_settings = {
+            let queue = match self.video_queue.lock() {
+                Ok(q) => q,
+                Err(_) => return,
+            };
+            match self.next_waiting_index(&queue) {
+                Some(index) => (queue[index].settings_override.clone(), queue[index].content_type),
+                None => return,
+            }
+        };
+        let mut config = next_settings.0.unwrap_or_else(|| self.config.clone());
+        if let Some(content_type) = next_settings.1 {
+            config.content_type = content_type;
+        }
+
+        // other programs (e.g. screen recorders) can hold nvenc sessions, so wait for one to free up
+        if config.encoder == Encoder::GpuNvenc && !self.nvenc_session_available(config.nvenc_session_limit) {
+            if let Ok(mut flag) = self.should_start_next.lock() {
+                *flag = true;
+            }
+            return;
+        }
+
+        let queue_item_path = {
+            let mut queue = match self.video_queue.lock() {
+                Ok(q) => q,
+                Err(_) => return,
+            };
+            let Some(index) = self.next_waiting_index(&queue) else {
+                return;
+            };
+
+            // ffmpeg reading and writing the same file destroys it, so refuse before it can start
+            let source = queue[index].image_sequence.as_ref().map_or(queue[index].path.clone(), |s| s.name_path());
+            let output_path = encode::output_path_for(&source, &config);
+            if let Some(clashing) = queue.iter().find(|i| utils::is_same_file(&i.path, &output_path)) {
+                let reason = if clashing.id == queue[index].id {
+                    "Output would overwrite the source".to_string()
+                } else {
+                    format!("Output would overwrite queued file {}", clashing.path.file_name().unwrap_or_default().to_string_lossy())
+                };
+                self.ffmpeg_log.lock().unwrap().push(format!("Skipped {}: {} ({}).", source.display(), reason.to_lowercase(), output_path.display()));
+                queue[index].status = FileStatus::Failed(reason);
+                drop(queue);
+                self.start_ffmpeg_thread();
+                return;
+            }
+
+            if let Some(item) = queue.get_mut(index) {
+                item.status = FileStatus::Processing;
+                // the encode overwrites any leftover that wasn't kept
+                item.leftover_output = None;
+                let part = SourcePart { trim: item.trim, video_stream: item.video_stream };
+                Some((item.id, item.path.clone(), item.size_bytes, part, item.image_sequence.clone()))
+            } else {
+                None
+            }
+        };
+
+        let Some((item_id, queue_item, source_size_bytes, part, sequence)) = queue_item_path else {
+            return;
+        };
+
+        self.spawn_job(vec![item_id], config.encoder.clone(), move |log_tx, current_child, cancel_requested| {
+            match sequence {
+                Some(sequence) => encode::compress_image_sequence(&sequence, source_size_bytes, &config, log_tx, current_child, cancel_requested),
+                None => encode::compress(&queue_item, source_size_bytes, part, &config, log_tx, current_child, cancel_requested),
+            }
+        });
+    }
+
+    // joins every waiting file into one output, in queue order
+    // why the waiting files can't be merged with the current settings, the merge is one encode with
+    // the global settings and a single pass
+    fn merge_blocker(&self) -> Option<&'static str> {
+        if self.config.bitrate_mode.separate_passes(&self.config.encoder) {
+            return Some("Merging can't do two-pass encoding, pick another bitrate mode to merge");
+        }
+        let own_settings = self.video_queue.lock().unwrap().iter()
+            .filter(|i| i.status == FileStatus::Waiting && i.image_sequence.is_none())
+            .any(|i| i.settings_override.is_some() || i.content_type.is_some());
+        own_settings.then_some(
+            "Some waiting files have their own settings or content type, which a merge can't apply. \
+            Reset their content type and re-add files with their own settings to merge",
+        )
+    }
+
+    fn start_merge(&mut self) {
+        if self.ffmpeg_busy.load(Ordering::SeqCst) || self.merge_blocker().is_some() {
+            return;
+        }
+
+        let (item_ids, inputs, video_streams): (Vec<u64>, Vec<PathBuf>, Vec<Option<u32>>) = {
+            let mut queue = match self.video_queue.lock() {
+                Ok(q) => q,
+                Err(_) => return,
+            };
+            // image sequences have no audio or probeable duration to join with
+            let mut merged = (Vec::new(), Vec::new(), Vec::new());
+            for item in queue.iter_mut().filter(|i| i.status == FileStatus::Waiting && i.image_sequence.is_none()) {
+                item.status = FileStatus::Processing;
+                merged.0.push(item.id);
+                merged.1.push(item.path.clone());
+                merged.2.push(item.video_stream);
+            }
+            merged
+        };
+        if inputs.is_empty() {
+            return;
+        }
+
+        let config = self.config.clone();
+        self.spawn_job(item_ids, config.encoder.clone(), move |log_tx, current_child, cancel_requested| {
+            encode::compress_merged(&inputs, &video_streams, &config, log_tx, current_child, cancel_requested);
+        });
+    }
+
+    // encode one waiting file onto the end of an earlier output picked by the user
+    fn start_append(&mut self, item_id: u64) {
+        if self.ffmpeg_busy.load(Ordering::SeqCst) {
+            return;
+        }
+        let Some(existing) = rfd::FileDialog::new()
+            .set_title("Append to existing output")
+            .add_filter("Video", &["mp4", "mkv", "mov", "m4v"])
+            .pick_file()
+        else {
+            return;
+        };
+
+        let input = {
+            let Ok(mut queue) = self.video_queue.lock() else {
+                return;
+            };
+            let Some(item) = queue.iter_mut().find(|i| i.id == item_id && i.status == FileStatus::Waiting) else {
+                return;
+            };
+            item.status = FileStatus::Processing;
+            item.path.clone()
+        };
+
+        let config = self.config.clone();
+        self.spawn_job(vec![item_id], config.encoder.clone(), move |log_tx, current_child, cancel_requested| {
+            encode::append_to_output(&input, &existing, &config, log_tx, current_child, cancel_requested);
+        });
+    }
+
+    // runs an encode on a worker thread and applies its status messages to the given queue items.
+    // the output size is shown on the first item only, since a merged job writes a single file
+    fn spawn_job<F>(&mut self, item_ids: Vec<u64>, encoder: Encoder, job: F)
+    where
+        F: FnOnce(&Sender<String>, &Mutex<Option<Child>>, &AtomicBool) + Send + 'static,
+    {
+        self.ffmpeg_busy.store(true, Ordering::SeqCst);
+        self.cancel_requested.store(false, Ordering::SeqCst);
+
+        // timing and encoder for the session summary
+        if let Ok(mut queue) = self.video_queue.lock() {
+            for item in queue.iter_mut().filter(|i| item_ids.contains(&i.id)) {
+                item.started_at = Some(Instant::now());
+                item.elapsed = None;
+                item.encoder_used = Some(encoder.clone());
+                item.ffmpeg_command = None;
+            }
+        }
+
+        // each run starts a fresh log for its items
+        if let Ok(mut item_logs) = self.item_logs.lock() {
+            for item_id in &item_ids {
+                item_logs.insert(*item_id, Vec::new());
+            }
+        }
+
+        let log_arc = Arc::clone(&self.ffmpeg_log);
+        let item_logs = Arc::clone(&self.item_logs);
+        let busy_flag = Arc::clone(&self.ffmpeg_busy);
+        let current_child = Arc::clone(&self.current_child);
+        let cancel_requested = Arc::clone(&self.cancel_requested);
+        let should_start_next_clone = Arc::clone(&self.should_start_next);
+        let stop_after_current = Arc::clone(&self.stop_after_current);
+        let video_queue_clone = Arc::clone(&self.video_queue);
+        let size_samples = Arc::clone(&self.size_samples);
+        let refresh_interval = Duration::from_secs_f64(1.0 / self.config.ui_refresh_hz.max(1) as f64);
+        let (log_tx, log_rx): (Sender<String>, Receiver<String>) = mpsc::channel();
+
+        thread::spawn(move || {
+            job(&log_tx, &current_child, &cancel_requested);
+            log_tx.send("[done]".to_string()).ok();
+        });
+
+        thread::spawn(move || {
+            let update_items = |update: &dyn Fn(&mut QueueItem)| {
+                if let Ok(mut queue) = video_queue_clone.lock() {
+                    queue.iter_mut().filter(|i| item_ids.contains(&i.id)).for_each(update);
+                }
+            };
+            let flush = |pending: &mut PendingUpdates| {
+                let PendingUpdates { progress, speed, lines } = std::mem::take(pending);
+                if progress.is_some() || speed.is_some() {
+                    update_items(&|item| {
+                        item.progress = progress.or(item.progress);
+                        item.speed = speed.or(item.speed);
+                    });
+                }
+                if lines.is_empty() {
+                    return;
+                }
+                if let Ok(mut item_logs) = item_logs.lock() {
+                    for item_id in &item_ids {
+                        item_logs.entry(*item_id).or_default().extend(lines.iter().cloned());
+                    }
+                }
+                if let Ok(mut log) = log_arc.lock() {
+                    log.extend(lines);
+                }
+            };
+            let mut pending = PendingUpdates::default();
+            let mut last_flush = Instant::now();
+            let mut predicted_size = None;
+            loop {
+                let line = match log_rx.recv_timeout(refresh_interval.saturating_sub(last_flush.elapsed())) {
+                    Ok(line) => Some(line),
+                    Err(mpsc::RecvTimeoutError::Timeout) => None,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                };
+                let held_back = line.as_deref().is_none_or(|line| pending.push(line));
+                // status tags go out straight away, after whatever came before them
+                if !held_back || last_flush.elapsed() >= refresh_interval {
+                    flush(&mut pending);
+                    last_flush = Instant::now();
+                }
+                let Some(line) = line.filter(|_| !held_back) else {
+                    continue;
+                };
+
+                // when job completes, update flags and file status
+                if line == "[done]" {
+                    busy_flag.store(false, Ordering::SeqCst);
+                    update_items(&|item| {
+                        if item.status == FileStatus::Processing {
+                            item.status = FileStatus::Done;
+                        }
+                        item.elapsed = item.started_at.map(|started| started.elapsed());
+                        item.progress = None;
+                        item.speed = None;
+                        item.stage = None;
+                    });
+                    if stop_after_current.swap(false, Ordering::SeqCst) {
+                        if let Ok(mut log) = log_arc.lock() {
+                            log.push("Stopped after the current file as requested.".to_string());
+                        }
+                    } else if let Ok(mut flag) = should_start_next_clone.lock() {
+                        *flag = true;
+                    }
+                } else if let Some(stage) = line.strip_prefix("[stage]:") {
+                    let stage = (!stage.is_empty()).then(|| stage.to_string());
+                    update_items(&|item| item.stage = stage.clone());
+                } else if line == "[cancelled]" {
+                    update_items(&|item| item.status = FileStatus::Waiting);
+                } else if line == "[already_optimal]" {
+                    update_items(&|item| item.status = FileStatus::AlreadyOptimal);
+                } else if let Some(reason) = line.strip_prefix("[failed]:") {
+                    update_items(&|item| item.status = FileStatus::Failed(reason.to_string()));
+                } else if let Some(command) = line.strip_prefix("[command]:") {
+                    update_items(&|item| item.ffmpeg_command = Some(command.to_string()));
+                } else if let Some(size_str) = line.strip_prefix("[predicted_size]:") {
+                    predicted_size = size_str.parse::<u64>().ok();
+                } else if let Some(size_str) = line.strip_prefix("[output_size]:") {
+                    let Ok(size) = size_str.parse::<u64>() else {
+                        continue;
+                    };
+                    if let Some(predicted) = predicted_size.take()
+                        && let Ok(mut samples) = size_samples.lock()
+                    {
+                        samples.push((predicted, size));
+                    }
+                    if let Ok(mut queue) = video_queue_clone.lock()
+                        && let Some(item) = queue.iter_mut().find(|i| i.id == item_ids[0])
+                    {
+                        item.output_size_bytes = Some(size);
+                    }
+                } else {
+                    // an unknown tag is still worth showing
+                    pending.lines.push(line);
+                }
+            }
+            flush(&mut pending);
+        });
+    }
+
+    // clicking the active column flips the direction, another column starts ascending
+    fn sort_header(&mut self, ui: &mut egui::Ui, column: QueueSortColumn, label: &str) {
+        let text = match self.queue_sort {
+            Some((active, false)) if active == column => format!("{} ⏶", label),
+            Some((active, true)) if active == column => format!("{} ⏷", label),
+            _ => label.to_string(),
+        };
+        if ui.add(egui::Button::new(egui::RichText::new(text).strong()).frame(false)).clicked() {
+            self.queue_sort = match self.queue_sort {
+                Some((active, descending)) if active == column => Some((column, !descending)),
+                _ => Some((column, false)),
+            };
+        }
+    }
+
+    // the running file always stays on top, everything else follows the chosen column
+    fn sort_queue_for_display(&self, queue: &mut [QueueItem]) {
+        let Some((column, descending)) = self.queue_sort else {
+            return;
+        };
+        let status_rank = |status: &FileStatus| match status {
+            FileStatus::Processing => 0,
+            FileStatus::Waiting => 1,
+            FileStatus::Done => 2,
+            FileStatus::AlreadyOptimal => 3,
+            FileStatus::Failed(_) => 4,
+        };
+        queue.sort_by(|a, b| {
+            let pinned = (b.status == FileStatus::Processing).cmp(&(a.status == FileStatus::Processing));
+            let order = match column {
+                QueueSortColumn::Status => status_rank(&a.status).cmp(&status_rank(&b.status)),
+                QueueSortColumn::Filename => {
+                    let name = |item: &QueueItem| item.path.file_name().unwrap_or_default().to_string_lossy().to_lowercase();
+                    name(a).cmp(&name(b))
+                }
+                QueueSortColumn::InputSize => a.size_bytes.cmp(&b.size_bytes),
+                QueueSortColumn::OutputSize => a.output_size_bytes.cmp(&b.output_size_bytes),
+            };
+            pinned.then(if descending { order.reverse() } else { order })
+        });
+    }
+
+    // encode a test pattern in the background with the current encoder settings
+    fn start_self_test(&self) {
+        if self.self_test_running.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let config = self.config.clone();
+        let running = Arc::clone(&self.self_test_running);
+        let message = Arc::clone(&self.self_test_message);
+        let log = Arc::clone(&self.ffmpeg_log);
+
+        thread::spawn(move || {
+            let encoder_name = match config.encoder {
+                Encoder::CpuX264 => "libx264",
+                Encoder::GpuNvenc => "h264_nvenc",
+            };
+            let result = match encode::run_self_test(&config) {
+                Ok(speed) => format!("✔ {} works, encoded the test clip at {:.1}x speed", encoder_name, speed),
+                Err(err) => {
+                    if let Ok(mut log) = log.lock() {
+                        log.push(format!("Encoder test with {} failed: {}", encoder_name, err));
+                    }
+                    format!("⚠ {} failed, see Debug Output for details", encoder_name)
+                }
+            };
+            if let Ok(mut message) = message.lock() {
+                *message = Some(result);
+            }
+            running.store(false, Ordering::SeqCst);
+        });
+    }
+
+    // exponential moving average, so the correction follows recent encodes without jumping on one outlier
+    fn apply_size_samples(&mut self) {
+        let samples: Vec<(u64, u64)> = match self.size_samples.lock() {
+            Ok(mut samples) => samples.drain(..).collect(),
+            Err(_) => return,
+        };
+        for (predicted, actual) in samples {
+            if predicted == 0 {
+                continue;
+            }
+            let ratio = actual as f64 / predicted as f64;
+            self.config.size_correction = (self.config.size_correction * (1.0 - SIZE_CORRECTION_WEIGHT) + ratio * SIZE_CORRECTION_WEIGHT)
+                .clamp(encode::MIN_SIZE_CORRECTION, encode::MAX_SIZE_CORRECTION);
+            self.config.size_correction_samples += 1;
+            self.config_dirty = true;
+        }
+    }
+
+    // persist settings changed anywhere this frame
+    fn store_config_if_dirty(&mut self) {
+        if self.config_dirty {
+            match confy::store(PROGRAM_CONFIG_NAME, None, &self.config) {
+                Ok(()) => {
+                    self.config_saved_at = Some(Instant::now());
+                    self.config_save_error = None;
+                }
+                Err(err) => self.config_save_error = Some(err.to_string()),
+            }
+            self.config_dirty = false;
+        }
+    }
+
+    // drop zone, target size and start/progress only, for a small window
+    fn compact_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let label_id = ui.label("Target size:").id;
+            if ui.add(egui::DragValue::new(&mut self.config.target_size_mb)).labelled_by(label_id).changed() {
+                self.config_dirty = true;
+            }
+            ui.label(self.config.size_unit.label());
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.button("Full View").on_hover_text("Show the tabs and all options").clicked() {
+                    self.config.compact_mode = false;
+                    self.config_dirty = true;
+                }
+            });
+        });
+        ui.separator();
+
+        let queue = self.video_queue.lock().unwrap().clone();
+        let waiting = queue.iter().filter(|i| i.status == FileStatus::Waiting).count();
+        let finished = queue.iter().filter(|i| matches!(i.status, FileStatus::Done | FileStatus::AlreadyOptimal)).count();
+        ui.vertical_centered(|ui| {
+            if queue.is_empty() {
+                ui.add_space(20.0);
+                ui.label(egui::RichText::new("📁").size(40.0));
+                ui.label(egui::RichText::new("Drop video files here").heading().weak());
+                return;
+            }
+
+            ui.label(format!("{} waiting, {} finished", waiting, finished));
+            if let Some(item) = queue.iter().find(|i| i.status == FileStatus::Processing) {
+                ui.spinner();
+                ui.label(item.path.file_name().unwrap_or_default().to_string_lossy());
+                let progress = item.progress.unwrap_or(0.0);
+                ui.add(egui::ProgressBar::new(progress).text(match &item.stage {
+                    Some(stage) => stage.clone(),
+                    None => format!("{:.0}%", progress * 100.0),
+                }));
+            } else if waiting > 0 {
+                if ui.add_sized(egui::vec2(200.0, 40.0), egui::Button::new(egui::RichText::new("Start").strong())).clicked() {
+                    self.start_run(false);
+                }
+            } else {
+                ui.label(egui::RichText::new("All done, drop more files to continue").weak());
+            }
+        });
+    }
+
+    // copies the command that ran for the item, or the one it would run with its current settings.
+    // building that probes the file, so it happens off the ui thread
+    fn copy_ffmpeg_command(&self, item_id: u64, ctx: &egui::Context) {
+        let Some((path, part, mut config, content_type, ran)) = self.video_queue.lock().unwrap().iter().find(|i| i.id == item_id).map(|i| (
+            i.path.clone(),
+            SourcePart { trim: i.trim, video_stream: i.video_stream },
+            i.settings_override.clone().unwrap_or_else(|| self.config.clone()),
+            i.content_type,
+            i.ffmpeg_command.clone(),
+        )) else {
+            return;
+        };
+        if let Some(content_type) = content_type {
+            config.content_type = content_type;
+        }
+        if let Some(command) = ran {
+            ctx.copy_text(command);
+            return;
+        }
+        let ctx = ctx.clone();
+        let log = Arc::clone(&self.ffmpeg_log);
+        thread::spawn(move || match encode::preview_command(&path, part, &config) {
+            Ok(command) => ctx.copy_text(command),
+            Err(err) => log.lock().unwrap().push(format!("Could not build the ffmpeg command for {}: {}", path.display(), err)),
+        });
+    }
+
+    // sizes in the queue and summary, rounded or to the byte depending on the setting
+    fn display_size(&self, bytes: u64) -> String {
+        if self.config.exact_sizes { utils::format_bytes(bytes) } else { utils::format_size(bytes) }
+    }
+
+    // disk space the waiting files will take, from each one's target size
+    fn output_estimate_footer(&self, ui: &mut egui::Ui, queue: &[QueueItem]) {
+        let waiting: Vec<&QueueItem> = queue.iter().filter(|i| i.status == FileStatus::Waiting).collect();
+        if waiting.is_empty() {
+            return;
+        }
+        let sizes: Vec<Option<u64>> = waiting.iter()
+            .map(|i| encode::expected_output_size(i.settings_override.as_ref().unwrap_or(&self.config), i.encoded_duration()))
+            .collect();
+        let total: u64 = sizes.iter().flatten().sum();
+        let unknown = sizes.iter().filter(|s| s.is_none()).count();
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            let text = match (total, unknown) {
+                (_, unknown) if unknown == waiting.len() => format!("{} waiting files, output size depends on the content", unknown),
+                (total, 0) => format!("Estimated output: ~{} for {} waiting files", utils::format_size(total), waiting.len()),
+                (total, unknown) => format!(
+                    "Estimated output: ~{} for {} waiting files, plus {} of unknown size",
+                    utils::format_size(total), waiting.len() - unknown, unknown,
+                ),
+            };
+            ui.label(egui::RichText::new(text).weak())
+                .on_hover_text("Each file aims a little under its target size, or follows its manual bitrate. Quality modes produce whatever size the content needs");
+        });
+    }
+
+    // seconds left for the running and waiting files, at the media seconds per wall second seen so far
+    // this session, falling back to the running file's reported speed before anything has finished
+    fn batch_eta(&self) -> Option<f64> {
+        let queue = self.video_queue.lock().unwrap();
+        let (mut encoded, mut spent, mut remaining) = (0.0, 0.0, 0.0);
+        let mut current_speed = None;
+        for item in queue.iter() {
+            let Some(duration) = item.encoded_duration() else {
+                continue;
+            };
+            match item.status {
+                FileStatus::Done | FileStatus::AlreadyOptimal => {
+                    if let Some(elapsed) = item.elapsed {
+                        encoded += duration;
+                        spent += elapsed.as_secs_f64();
+                    }
+                }
+                FileStatus::Processing => {
+                    let progress = item.progress.unwrap_or(0.0) as f64;
+                    remaining += duration * (1.0 - progress);
+                    current_speed = item.speed.map(|s| s as f64);
+                    if let Some(started_at) = item.started_at {
+                        encoded += duration * progress;
+                        spent += started_at.elapsed().as_secs_f64();
+                    }
+                }
+                FileStatus::Waiting if item.eligible => remaining += duration,
+                _ => {}
+            }
+        }
+        let speed = if encoded > 0.0 && spent > 0.0 { Some(encoded / spent) } else { current_speed };
+        speed.filter(|s| *s > 0.0).map(|s| remaining / s)
+    }
+
+    // frames for the trim timeline, extracted once per item the first time its details are opened
+    fn request_thumbnails(&mut self, item_id: u64, path: PathBuf, duration: f64) {
+        if !self.thumbnails_requested.insert(item_id) {
+            return;
+        }
+        let thumbnail_frames = Arc::clone(&self.thumbnail_frames);
+        thread::spawn(move || {
+            let frames = encode::extract_thumbnails(&path, duration, TIMELINE_THUMBNAILS);
+            if let Ok(mut thumbnail_frames) = thumbnail_frames.lock() {
+                thumbnail_frames.insert(item_id, frames);
+            }
+        });
+    }
+
+    fn load_thumbnails(&mut self, ctx: &egui::Context) {
+        let Ok(mut frames) = self.thumbnail_frames.lock() else {
+            return;
+        };
+        for (item_id, item_frames) in frames.drain() {
+            let textures = item_frames.iter().enumerate()
+                .map(|(index, rgba)| {
+                    let image = egui::ColorImage::from_rgba_unmultiplied([encode::THUMBNAIL_WIDTH, encode::THUMBNAIL_HEIGHT], rgba);
+                    ctx.load_texture(format!("thumbnail_{}_{}", item_id, index), image, egui::TextureOptions::LINEAR)
+                })
+                .collect();
+            self.thumbnails.insert(item_id, textures);
+        }
+    }
+
+    fn create_contact_sheet(&mut self, item_id: u64) {
+        let output_path = {
+            let queue = self.video_queue.lock().unwrap();
+            let Some(item) = queue.iter().find(|i| i.id == item_id) else {
+                return;
+            };
+            let config = item.settings_override.as_ref().unwrap_or(&self.config);
+            let source = item.image_sequence.as_ref().map_or(item.path.clone(), |s| s.name_path());
+            encode::output_path_for(&source, config)
+        };
+        if self.contact_sheet_running.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let running = Arc::clone(&self.contact_sheet_running);
+        let result = Arc::clone(&self.contact_sheet_result);
+        thread::spawn(move || {
+            let sheet = encode::create_contact_sheet(&output_path);
+            *result.lock().unwrap() = Some(sheet);
+            running.store(false, Ordering::SeqCst);
+        });
+    }
+
+    fn contact_sheet_ui(&mut self, ctx: &egui::Context) {
+        if let Some(result) = self.contact_sheet_result.lock().unwrap().take() {
+            self.contact_sheet = Some(result.map(|(path, rgba)| {
+                let image = egui::ColorImage::from_rgba_unmultiplied([encode::CONTACT_SHEET_WIDTH, encode::CONTACT_SHEET_HEIGHT], &rgba);
+                (path, ctx.load_texture("contact_sheet", image, egui::TextureOptions::LINEAR))
+            }));
+        }
+        let Some(sheet) = &self.contact_sheet else {
+            return;
+        };
+        let mut open = true;
+        egui::Window::new("Contact Sheet")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| match sheet {
+                Ok((path, texture)) => {
+                    let max_width = (ctx.screen_rect().width() - 60.0).min(texture.size_vec2().x);
+                    ui.add(egui::Image::new(texture).max_width(max_width));
+                    ui.horizontal(|ui| {
+                        ui.label(format!("Saved to {}", path.display()));
+                        if ui.button("Copy Path").clicked() {
+                            ctx.copy_text(path.display().to_string());
+                        }
+                    });
+                }
+                Err(err) => {
+                    ui.colored_label(ui.visuals().error_fg_color, format!("Could not create the contact sheet: {}", err));
+                }
+            });
+        if !open {
+            self.contact_sheet = None;
+        }
+    }
+
+    fn shutdown_countdown_ui(&mut self, ctx: &egui::Context) {
+        let Some(shutdown_at) = self.shutdown_at else {
+            return;
+        };
+        let remaining = shutdown_at.saturating_duration_since(Instant::now());
+        let mut shut_down_now = remaining.is_zero();
+        let mut cancelled = false;
+        let modal = egui::Modal::new(egui::Id::new("shutdown_countdown")).show(ctx, |ui| {
+            ui.heading("Batch finished");
+            ui.label(format!("The computer shuts down in {} seconds.", remaining.as_secs_f32().ceil()));
+            ui.separator();
+            ui.horizontal(|ui| {
+                cancelled = ui.button("Cancel").clicked();
+                shut_down_now |= ui.button("Shut Down Now").clicked();
+            });
+        });
+        if cancelled || modal.should_close() {
+            self.shutdown_at = None;
+        } else if shut_down_now {
+            self.shutdown_at = None;
+            self.store_config_if_dirty();
+            if let Err(err) = utils::shut_down_computer() {
+                self.ffmpeg_log.lock().unwrap().push(format!("Failed to shut down: {}", err));
+            }
+        }
+    }
+
+    fn item_log_ui(&mut self, ctx: &egui::Context) {
+        let Some(item_id) = self.log_window_item else {
+            return;
+        };
+        let name = self.video_queue.lock().unwrap().iter()
+            .find(|i| i.id == item_id)
+            .map(|i| i.path.file_name().unwrap_or_default().to_string_lossy().into_owned());
+        let Some(name) = name else {
+            self.log_window_item = None;
+            return;
+        };
+        let mut open = true;
+        egui::Window::new(format!("Log: {}", name))
+            .id(egui::Id::new("item_log"))
+            .open(&mut open)
+            .default_size(egui::vec2(600.0, 300.0))
+            .show(ctx, |ui| {
+                let item_logs = self.item_logs.lock().unwrap();
+                let lines = item_logs.get(&item_id).map_or(&[][..], |lines| lines.as_slice());
+                if ui.button("Copy").clicked() {
+                    ctx.copy_text(lines.join("\n"));
+                }
+                egui::ScrollArea::vertical()
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        for line in lines {
+                            ui.label(line);
+                        }
+                    });
+            });
+        if !open {
+            self.log_window_item = None;
+        }
+    }
+
+    // finished files of this session with their sizes, timing and encoder
+    fn summary_ui(&mut self, ui: &mut egui::Ui) {
+        let finished: Vec<QueueItem> = self.video_queue.lock().unwrap().iter()
+            .filter(|i| matches!(i.status, FileStatus::Done | FileStatus::AlreadyOptimal))
+            .cloned()
+            .collect();
+        if finished.is_empty() {
+            ui.label(egui::RichText::new("Finished files will be listed here").weak());
+            return;
+        }
+
+        let total_input: u64 = finished.iter().map(|i| i.size_bytes).sum();
+        let total_output: u64 = finished.iter().map(|i| i.output_size_bytes.unwrap_or(i.size_bytes)).sum();
+        let total_time: Duration = finished.iter().filter_map(|i| i.elapsed).sum();
+        ui.horizontal(|ui| {
+            ui.label(format!(
+                "{} files, {} → {} ({:.0}% of the original) in {}",
+                finished.len(),
+                self.display_size(total_input),
+                self.display_size(total_output),
+                total_output as f64 / total_input.max(1) as f64 * 100.0,
+                utils::format_duration(total_time.as_secs_f64()),
+            ));
+            if ui.button("Copy as CSV").clicked() {
+                ui.ctx().copy_text(summary_csv(&finished));
+            }
+        });
+        ui.separator();
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("summary_grid").striped(true).show(ui, |ui| {
+                for header in ["File", "Input", "Output", "Ratio", "Time", "Encoder"] {
+                    ui.label(egui::RichText::new(header).strong());
+                }
+                ui.end_row();
+
+                for item in &finished {
+                    ui.label(item.path.file_name().unwrap_or_default().to_string_lossy());
+                    ui.label(self.display_size(item.size_bytes));
+                    match item.output_size_bytes {
+                        Some(size) => {
+                            ui.label(self.display_size(size));
+                            ui.label(format!("{:.0}%", size as f64 / item.size_bytes.max(1) as f64 * 100.0));
+                        }
+                        None => {
+                            ui.label("kept original");
+                            ui.label("-");
+                        }
+                    }
+                    ui.label(item.elapsed.map_or("-".to_string(), |e| utils::format_duration(e.as_secs_f64())));
+                    ui.label(item.encoder_used.as_ref().map_or("-", encoder_name));
+                    ui.end_row();
+                }
+            });
+        });
+    }
+
+    fn apply_theme(&mut self, ctx: &egui::Context) {
+        if self.config.dark_mode_enabled {
+            ctx.set_theme(egui::Theme::Dark);
+        } else {
+            ctx.set_theme(egui::Theme::Light);
+        }
+    }
+}
+
+impl eframe::App for MyApp {
+    fn update(&mut self, ctx: &egui::Context, _: &mut eframe::Frame) {
+        ctx.set_zoom_factor(1.2);
+        self.apply_theme(ctx);
+        
+        // Drag & drop handler, runs on every tab and brings the queue into view
+        let dropped_files = ctx.input(|i| i.raw.dropped_files.clone());
+        if !dropped_files.is_empty() {
+            self.current_tab = Tab::Main;
+        }
+        for file in dropped_files {
+            if let Some(path) = file.path {
+                self.add_path(path);
+            }
+        }
+
+        // another launch handed over its files and exited, bring this window forward instead
+        let forwarded: Vec<Vec<PathBuf>> = self.instance_rx.as_ref().map_or_else(Vec::new, |rx| rx.try_iter().collect());
+        for files in forwarded {
+            self.current_tab = Tab::Main;
+            for path in files {
+                self.add_path(path);
+            }
+            ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(false));
+            ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+        }
+
+        self.apply_size_samples();
+        self.load_thumbnails(ctx);
+        self.check_for_source_changes();
+
+        // Automatically start next compression job if flagged
+        if !self.ffmpeg_busy.load(Ordering::SeqCst) && !self.video_queue.lock().unwrap().is_empty() {
+            let should_start = {
+                if let Ok(mut flag) = self.should_start_next.lock() {
+                    if *flag {
+                        *flag = false;
+                        true
+                    } else {
+                        false
+                    }
+                } else {
+                    false
+                }
+            };
+
+            if should_start {
+                self.start_ffmpeg_thread();
+                // nothing was started and nothing is waiting on nvenc, so the batch is over
+                if !self.ffmpeg_busy.load(Ordering::SeqCst) && !*self.should_start_next.lock().unwrap() {
+                    if self.config.completion_sound {
+                        utils::play_completion_sound();
+                    }
+                    if self.shutdown_when_done {
+                        self.shutdown_when_done = false;
+                        self.shutdown_at = Some(Instant::now() + SHUTDOWN_COUNTDOWN);
+                    }
+                }
+            }
+        }
+
+        // compact mode swaps the whole window for the minimal view
+        if self.config.compact_mode {
+            egui::CentralPanel::default().show(ctx, |ui| self.compact_ui(ui));
+            self.store_config_if_dirty();
+            ctx.request_repaint();
+            return;
+        }
+
+        // ctrl+1-4 switch tabs, tab and space reach everything else
+        let tab_shortcuts = [(egui::Key::Num1, Tab::Main), (egui::Key::Num2, Tab::Options), (egui::Key::Num3, Tab::Summary), (egui::Key::Num4, Tab::Output)];
+        for (key, tab) in tab_shortcuts {
+            if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, key)) {
+                self.current_tab = tab;
+            }
+        }
+
+        // Draw top tab bar
+        egui::TopBottomPanel::top("tabs").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui.selectable_label(matches!(self.current_tab, Tab::Main), "Main").on_hover_text("Ctrl+1").clicked() {
+                    self.current_tab = Tab::Main;
+                }
+                if ui.selectable_label(matches!(self.current_tab, Tab::Options), "Options").on_hover_text("Ctrl+2").clicked() {
+                    self.current_tab = Tab::Options;
+                }
+                if ui.selectable_label(matches!(self.current_tab, Tab::Summary), "Summary").on_hover_text("Ctrl+3").clicked() {
+                    self.current_tab = Tab::Summary;
+                }
+                if ui.selectable_label(matches!(self.current_tab, Tab::Output), "Debug Output").on_hover_text("Ctrl+4").clicked() {
+                    self.current_tab = Tab::Output;
+                }
+
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if self.config_dirty {
+                        ui.label(egui::RichText::new("Saving...").weak());
+                    } else if let Some(err) = &self.config_save_error {
+                        ui.label(egui::RichText::new("⚠ Settings not saved").color(ui.visuals().warn_fg_color))
+                            .on_hover_text(err);
+                    } else if self.config_saved_at.is_some_and(|at| at.elapsed() < Duration::from_secs(2)) {
+                        ui.label(egui::RichText::new("✔ Settings saved").weak());
+                    }
+                });
+            });
+        });
+
+        // Dismissable banner for a newer release
+        let available_update = self.available_update.lock().unwrap().clone();
+        if let Some((tag, url)) = available_update
+            && !self.update_banner_dismissed
+        {
+            egui::TopBottomPanel::top("update_banner").show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(format!("Update available: {}", tag));
+                    ui.hyperlink_to("Download", url);
+                    if ui.button("Dismiss").clicked() {
+                        self.update_banner_dismissed = true;
+                    }
+                });
+            });
+        }
+
+        // Main panel based on current tab
+        egui::CentralPanel::default().show(ctx, |ui| {
+            match self.current_tab {
+                Tab::Main => {
+                    let mut queue = self.video_queue.lock().unwrap().clone();
+                    self.sort_queue_for_display(&mut queue);
+                    // stable, so the chosen column order holds inside each group
+                    if self.config.group_queue_by_status {
+                        queue.sort_by_key(|i| status_group(&i.status).0);
+                    }
+                    if queue.is_empty() {
+                        // Add space to center vertically
+                        let available_height = ui.available_height();
+                        let prompt_height = 240.0;
+                        ui.add_space((available_height - prompt_height) / 2.0);
+
+                        ui.vertical_centered(|ui| {
+                            ui.label(egui::RichText::new("📁").size(40.0));
+                            ui.label(egui::RichText::new("Drop video files here to begin").heading().weak());
+                            ui.add_space(8.0);
+                            if ui.add_sized(egui::vec2(160.0, 32.0), egui::Button::new("Add Files...")).clicked() {
+                                self.pick_files_to_add();
+                            }
+                            if ui.link("or import a list of files").clicked() {
+                                self.pick_list_to_import();
+                            }
+                            if !self.config.recent_files.is_empty() {
+                                self.recent_files_menu(ui);
+                            }
+                            ui.add_space(12.0);
+                            ui.label(format!("Each file will be compressed to {}", self.config.summary()));
+                            if ui.link("Change settings").clicked() {
+                                self.current_tab = Tab::Options;
+                            }
+                            ui.add_space(12.0);
+                            ui.label(egui::RichText::new(
+                                "Works with MP4, MKV, MOV, AVI, WebM and most other formats ffmpeg reads, as well as folders of numbered images"
+                            ).weak().small());
+                        });
+                    } else {
+                        if self.ffmpeg_busy.load(Ordering::SeqCst) {
+                            ui.horizontal(|ui| {
+                                ui.add_enabled(false, egui::Button::new(egui::RichText::new("Compressing...")).min_size(egui::vec2(200.0, 40.0)));
+                                ui.spinner();
+
+                                // lets the running file finish but keeps the queue from moving on
+                                let stopping = self.stop_after_current.load(Ordering::SeqCst);
+                                let label = if stopping { "Keep Going" } else { "Stop After Current" };
+                                if ui.add_sized(egui::vec2(140.0, 40.0), egui::Button::new(label)).clicked() {
+                                    self.stop_after_current.store(!stopping, Ordering::SeqCst);
+                                }
+                                if stopping {
+                                    ui.label(egui::RichText::new("Will stop after the current file").color(ui.visuals().warn_fg_color));
+                                }
+                                if let Some(eta) = self.batch_eta() {
+                                    ui.label(format!("Batch ETA: {}", utils::format_eta(eta)))
+                                        .on_hover_text("Time left for the running and waiting files at the speed seen so far");
+                                }
+                            });
+                        } else {
+                            let (waiting, selected_waiting) = {
+                                let queue = self.video_queue.lock().unwrap();
+                                let waiting = queue.iter().filter(|i| i.status == FileStatus::Waiting);
+                                (waiting.clone().count(), waiting.filter(|i| self.selected_items.contains(&i.id)).count())
+                            };
+                            ui.horizontal(|ui| {
+                                if ui
+                                    .add_sized(
+                                        egui::vec2(200.0, 40.0),
+                                        egui::Button::new(egui::RichText::new("Start All").strong()).wrap(),
+                                    )
+                                    .clicked()
+                                {
+                                    self.start_run(false);
+                                }
+                                if ui
+                                    .add_enabled_ui(selected_waiting > 0, |ui| {
+                                        ui.add_sized(egui::vec2(140.0, 40.0), egui::Button::new(format!("Start Selected ({})", selected_waiting)))
+                                    })
+                                    .inner
+                                    .on_hover_text("Only compress the checked files, the rest stay waiting")
+                                    .clicked()
+                                {
+                                    self.start_run(true);
+                                }
+                            });
+                            let poor_items = self.poor_quality_items(false);
+                            if !poor_items.is_empty() {
+                                ui.horizontal(|ui| {
+                                    let files = if poor_items.len() == 1 { "file" } else { "files" };
+                                    ui.label(egui::RichText::new(format!(
+                                        "⚠ {} {} will look poor at {} {}, consider lowering the resolution",
+                                        poor_items.len(), files, self.config.target_size_mb, self.config.size_unit.label(),
+                                    )).color(ui.visuals().warn_fg_color))
+                                        .on_hover_text(poor_items.iter().map(|(_, name)| name.as_str()).collect::<Vec<_>>().join("\n"));
+                                    if ui.button("Lower Resolution for These")
+                                        .on_hover_text("Gives each of these files its own settings with the largest of 1080p, 720p or 480p that looks at least fair")
+                                        .clicked()
+                                    {
+                                        let item_ids: Vec<u64> = poor_items.iter().map(|(id, _)| *id).collect();
+                                        self.lower_resolution_for(&item_ids);
+                                    }
+                                });
+                            }
+                            if waiting > 1 {
+                                let blocker = self.merge_blocker();
+                                if ui.add_enabled(blocker.is_none(), egui::Button::new("Merge into One File"))
+                                    .on_hover_text("Join all waiting files, in queue order, into a single output sized to the target")
+                                    .on_disabled_hover_text(blocker.unwrap_or_default())
+                                    .clicked()
+                                {
+                                    self.start_merge();
+                                }
+                            }
+                        }
+                        ui.separator();
+
+                        ui.horizontal(|ui| {
+                            ui.label("Queue:");
+                            if ui.small_button("Add Files...").clicked() {
+                                self.pick_files_to_add();
+                            }
+                            if ui.small_button("Import List...").on_hover_text("Add every file from a text file with one path per line").clicked() {
+                                self.pick_list_to_import();
+                            }
+                            if ui.small_button("Image Sequence...").on_hover_text("Add numbered images, like a timelapse, as one video. Folders of images can also be dropped").clicked() {
+                                self.pick_image_sequence();
+                            }
+                            self.recent_files_menu(ui);
+                            if ui.small_button("Fit All to Target")
+                                .on_hover_text("Lowers the resolution of waiting files that would look poor at their target size, \
+                                    trying 1080p, 720p and 480p until the estimate is acceptable. Each changed file gets its own settings")
+                                .clicked()
+                            {
+                                self.fit_all_to_target();
+                            }
+                            if ui.toggle_value(&mut self.config.group_queue_by_status, "Group by Status").changed() {
+                                self.config_dirty = true;
+                            }
+                            if ui.toggle_value(&mut self.config.newest_first, "Newest First")
+                                .on_hover_text("Process the most recently added waiting file next, so late additions don't wait behind the whole queue")
+                                .changed()
+                            {
+                                self.config_dirty = true;
+                            }
+                            let shutdown_label = if self.shutdown_when_done {
+                                egui::RichText::new("⏻ Shut Down When Done").color(ui.visuals().warn_fg_color)
+                            } else {
+                                egui::RichText::new("⏻ Shut Down When Done")
+                            };
+                            ui.toggle_value(&mut self.shutdown_when_done, shutdown_label)
+                                .on_hover_text("Turns the computer off after the last file finishes, unsaved work in other programs is lost. \
+                                    You get a minute to cancel. Stopping the queue early doesn't shut down");
+                            if !self.selected_items.is_empty() {
+                                ui.separator();
+                                ui.label(format!("{} selected", self.selected_items.len()));
+                                if ui.button("Remove Selected").clicked() {
+                                    self.remove_selected();
+                                }
+                                if ui.button("Retry Selected").clicked() {
+                                    self.retry_selected();
+                                }
+                                if ui.button("Apply Settings to Selected")
+                                    .on_hover_text("Encode the selected files with the current options, even if they change later")
+                                    .clicked()
+                                {
+                                    self.apply_settings_to_selected();
+                                }
+                            }
+                        });
+                        if let Some(report) = &self.fit_report {
+                            let mut dismissed = false;
+                            ui.horizontal(|ui| {
+                                ui.vertical(|ui| {
+                                    for line in report {
+                                        ui.label(egui::RichText::new(line).weak());
+                                    }
+                                });
+                                dismissed = ui.small_button("✖").on_hover_text("Dismiss").clicked();
+                            });
+                            if dismissed {
+                                self.fit_report = None;
+                            }
+                        }
+                        let mut compress_again = None;
+                        let mut prioritize = None;
+                        let mut resolve_leftover = None;
+                        let mut reprobe = None;
+                        let mut append = None;
+                        let mut contact_sheet = None;
+                        let mut copy_command = None;
+                        let mut view_log = None;
+                        let mut confirmation = None;
+                        let mut edited_label = None;
+                        let mut edited_video_stream = None;
+                        let mut edited_content_type = None;
+                        let mut edited_trim = None;
+                        let mut thumbnail_requests = Vec::new();
+                        let size_column_width = if self.config.exact_sizes { EXACT_SIZE_COLUMN_WIDTH } else { SIZE_COLUMN_WIDTH };
+                        ui.horizontal(|ui| {
+                            let mut all_selected = queue.iter().all(|i| self.selected_items.contains(&i.id));
+                            if named_checkbox(ui, &mut all_selected, "Select all files").changed() {
+                                if all_selected {
+                                    self.selected_items.extend(queue.iter().map(|i| i.id));
+                                } else {
+                                    self.selected_items.clear();
+                                }
+                            }
+                            // line up with the rows below, which start after the expand arrow
+                            ui.add_space(ui.spacing().icon_width);
+                            queue_cell(ui, STATUS_COLUMN_WIDTH, |ui| self.sort_header(ui, QueueSortColumn::Status, "Status"));
+                            queue_cell(ui, NAME_COLUMN_WIDTH, |ui| self.sort_header(ui, QueueSortColumn::Filename, "Filename"));
+                            queue_cell(ui, FORMAT_COLUMN_WIDTH, |ui| ui.label(egui::RichText::new("Format").strong()));
+                            queue_cell(ui, size_column_width, |ui| self.sort_header(ui, QueueSortColumn::InputSize, "Input Size"));
+                            queue_cell(ui, OUTPUT_COLUMN_WIDTH, |ui| self.sort_header(ui, QueueSortColumn::OutputSize, "Output Size"));
+                        });
+
+                        let mut current_group = None;
+                        for item in queue.iter() {
+                            if self.config.group_queue_by_status {
+                                let (rank, title) = status_group(&item.status);
+                                if current_group != Some(rank) {
+                                    current_group = Some(rank);
+                                    let count = queue.iter().filter(|i| status_group(&i.status).0 == rank).count();
+                                    ui.add_space(4.0);
+                                    ui.label(egui::RichText::new(format!("{} ({})", title, count)).strong().weak());
+                                }
+                            }
+                            let id = ui.make_persistent_id(("queue_item", item.id));
+                            egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), id, false)
+                                .show_header(ui, |ui| {
+                                    let mut selected = self.selected_items.contains(&item.id);
+                                    let select_name = format!("Select {}", item.path.file_name().unwrap_or_default().to_string_lossy());
+                                    if named_checkbox(ui, &mut selected, &select_name).changed() {
+                                        if selected {
+                                            self.selected_items.insert(item.id);
+                                        } else {
+                                            self.selected_items.remove(&item.id);
+                                        }
+                                    }
+                                    let emoji = match item.status {
+                                        FileStatus::Waiting => "🕓",
+                                        FileStatus::Processing => "🔄",
+                                        FileStatus::Done => "✅",
+                                        FileStatus::AlreadyOptimal => "➖",
+                                        FileStatus::Failed(_) => "❌",
+                                    };
+                                    queue_cell(ui, STATUS_COLUMN_WIDTH, |ui| {
+                                        if item.status == FileStatus::AlreadyOptimal {
+                                            ui.label(emoji).with_accessible_name("Already optimal").on_hover_text("Already optimal: the output was not smaller than the source and was discarded");
+                                        } else if let FileStatus::Failed(reason) = &item.status {
+                                            ui.label(emoji).with_accessible_name(format!("Failed: {}", reason)).on_hover_text(format!("Failed: {}", reason));
+                                        } else if item.status == FileStatus::Processing {
+                                            ui.add(egui::Spinner::new()).with_accessible_name("Processing");
+                                        } else if item.status == FileStatus::Waiting && !item.eligible {
+                                            ui.label(egui::RichText::new(emoji).weak()).with_accessible_name("Skipped").on_hover_text("Skipped by the current run, start it again to include it");
+                                        } else {
+                                            ui.label(emoji).with_accessible_name(status_name(&item.status));
+                                        }
+                                        if item.changed_on_disk {
+                                            ui.label(egui::RichText::new("⚠").color(ui.visuals().warn_fg_color))
+                                                .with_accessible_name("Changed on disk")
+                                                .on_hover_text("File changed on disk since it was added, re-probe to refresh its details");
+                                        }
+                                        if item.leftover_output.is_some() {
+                                            ui.label(egui::RichText::new("⚠").color(ui.visuals().warn_fg_color))
+                                                .with_accessible_name("Unfinished output found")
+                                                .on_hover_text("An unfinished output from an earlier run was found, expand for options");
+                                        }
+                                    });
+                                    let file_name = item.path.file_name().unwrap_or_default().to_string_lossy();
+                                    queue_cell(ui, NAME_COLUMN_WIDTH, |ui| {
+                                        if !item.label.is_empty() {
+                                            ui.label(
+                                                egui::RichText::new(&item.label)
+                                                    .small()
+                                                    .background_color(ui.visuals().selection.bg_fill)
+                                                    .color(ui.visuals().selection.stroke.color),
+                                            );
+                                        }
+                                        if item.trim.is_some() {
+                                            ui.label("✂").on_hover_text("Trimmed, expand to see the kept part");
+                                        }
+                                        if item.settings_override.is_some() {
+                                            ui.add(egui::Label::new(format!("{} ⚙", file_name)).truncate())
+                                                .on_hover_text("Uses its own settings snapshot");
+                                        } else {
+                                            ui.add(egui::Label::new(file_name).truncate());
+                                        }
+                                    });
+                                    queue_cell(ui, FORMAT_COLUMN_WIDTH, |ui| match &item.media_info {
+                                        Some(info) => ui.add(egui::Label::new(egui::RichText::new(info.summary()).weak()).truncate()),
+                                        None => ui.label("-"),
+                                    });
+                                    queue_cell(ui, size_column_width, |ui| ui.label(self.display_size(item.size_bytes)));
+                                    queue_cell(ui, OUTPUT_COLUMN_WIDTH, |ui| match item.output_size_bytes {
+                                        _ if item.status == FileStatus::Processing => {
+                                            let progress = item.progress.unwrap_or(0.0);
+                                            let mut bar = egui::ProgressBar::new(progress).desired_width(OUTPUT_COLUMN_WIDTH - 10.0);
+                                            if let Some(stage) = &item.stage {
+                                                bar = bar.text(stage.as_str());
+                                            } else if let Some(duration) = item.media_info.as_ref().and_then(|i| i.duration) {
+                                                bar = bar.text(format!(
+                                                    "{:.0}% — {} / {}",
+                                                    progress * 100.0,
+                                                    utils::format_duration(progress as f64 * duration),
+                                                    utils::format_duration(duration),
+                                                ));
+                                            } else {
+                                                bar = bar.text(format!("{:.0}%", progress * 100.0));
+                                            }
+                                            let bar = ui.add(bar);
+                                            if let Some(speed) = item.speed {
+                                                bar.on_hover_text(format!("Encoding at {:.2}x speed", speed));
+                                            }
+                                        }
+                                        Some(size) if size >= item.size_bytes => {
+                                            ui.label(
+                                                egui::RichText::new(self.display_size(size)).color(ui.visuals().warn_fg_color)
+                                            ).on_hover_text("Output is larger than the source");
+                                        }
+                                        Some(size) => {
+                                            ui.label(self.display_size(size));
+                                        }
+                                        None => {
+                                            ui.label("-");
+                                        }
+                                    });
+                                })
+                                .body(|ui| {
+                                    egui::Grid::new(("queue_item_details", item.id)).show(ui, |ui| {
+                                        ui.label("Path:");
+                                        ui.label(item.path.display().to_string());
+                                        ui.end_row();
+
+                                        if let Some(info) = &item.media_info {
+                                            ui.label("Source:");
+                                            let mut details = vec![info.summary()];
+                                            if let (Some(width), Some(height)) = (info.width, info.height) {
+                                                details.push(format!("{}x{}", width, height));
+                                            }
+                                            if let Some(fps) = info.frame_rate {
+                                                details.push(format!("{:.2} fps", fps));
+                                            }
+                                            if let Some(duration) = info.duration {
+                                                details.push(utils::format_duration(duration));
+                                            }
+                                            ui.label(details.join(", "));
+                                            ui.end_row();
+                                        }
+
+                                        // sequences are always encoded whole
+                                        if item.image_sequence.is_none()
+                                            && let Some(duration) = item.media_info.as_ref().and_then(|i| i.duration)
+                                        {
+                                            ui.label("Trim:");
+                                            ui.vertical(|ui| {
+                                                if !self.thumbnails_requested.contains(&item.id) {
+                                                    thumbnail_requests.push((item.id, item.path.clone(), duration));
+                                                }
+                                                let thumbnails = self.thumbnails.get(&item.id).map_or(&[][..], |t| t.as_slice());
+                                                let editable = item.status == FileStatus::Waiting;
+                                                let timeline = ui.add_enabled_ui(editable, |ui| {
+                                                    trim_timeline(ui, ui.make_persistent_id(("trim", item.id)), duration, item.trim, thumbnails)
+                                                }).inner;
+                                                if editable && let Some(trim) = timeline {
+                                                    edited_trim = Some((item.id, trim));
+                                                }
+                                                ui.horizontal(|ui| {
+                                                    match item.trim {
+                                                        Some(trim) => {
+                                                            ui.label(format!(
+                                                                "{} to {} ({} kept)",
+                                                                utils::format_duration(trim.start),
+                                                                utils::format_duration(trim.end),
+                                                                utils::format_duration(trim.end - trim.start),
+                                                            ));
+                                                            if editable && ui.small_button("Clear").clicked() {
+                                                                edited_trim = Some((item.id, None));
+                                                            }
+                                                        }
+                                                        None => {
+                                                            ui.label(egui::RichText::new("Drag the handles to cut the start or end").weak());
+                                                        }
+                                                    }
+                                                });
+                                            });
+                                            ui.end_row();
+                                        }
+
+                                        if let Some(info) = item.media_info.as_ref().filter(|i| i.video_streams.len() > 1) {
+                                            let label_id = ui.label("Video stream:").id;
+                                            let selected = item.video_stream.or(info.default_video_stream());
+                                            let selected_text = info.video_streams.iter()
+                                                .find(|s| Some(s.index) == selected)
+                                                .map_or("-".to_string(), |s| s.label());
+                                            let enabled = item.status != FileStatus::Processing;
+                                            ui.add_enabled_ui(enabled, |ui| {
+                                                egui::ComboBox::from_id_salt(("video_stream_combo", item.id))
+                                                    .selected_text(selected_text)
+                                                    .show_ui(ui, |ui| {
+                                                        for stream in &info.video_streams {
+                                                            if ui.selectable_label(Some(stream.index) == selected, stream.label()).clicked() {
+                                                                edited_video_stream = Some((item.id, stream.index));
+                                                            }
+                                                        }
+                                                    }).response.labelled_by(label_id)
+                                                    .on_hover_text("The file has more than one video stream, cover art is skipped by default");
+                                            });
+                                            ui.end_row();
+                                        }
+
+                                        let label_id = ui.label("Content:").id;
+                                        let default_content_type = item.settings_override.as_ref().unwrap_or(&self.config).content_type;
+                                        let selected = item.content_type.unwrap_or(default_content_type);
+                                        ui.add_enabled_ui(item.status != FileStatus::Processing, |ui| {
+                                            egui::ComboBox::from_id_salt(("content_type_combo", item.id))
+                                                .selected_text(selected.label())
+                                                .show_ui(ui, |ui| {
+                                                    for content_type in ContentType::ALL {
+                                                        if ui.selectable_label(content_type == selected, content_type.label())
+                                                            .on_hover_text(content_type.description())
+                                                            .clicked()
+                                                        {
+                                                            edited_content_type = Some((item.id, content_type));
+                                                        }
+                                                    }
+                                                }).response.labelled_by(label_id)
+                                                .on_hover_text("Tunes the encoder for what this file shows, instead of the content type in Options");
+                                        });
+                                        ui.end_row();
+
+                                        let label_id = ui.label("Label:").id;
+                                        let mut label = item.label.clone();
+                                        if ui.add(egui::TextEdit::singleline(&mut label).hint_text("e.g. needs crop")).labelled_by(label_id).changed() {
+                                            edited_label = Some((item.id, label));
+                                        }
+                                        ui.end_row();
+
+                                        let settings = item.settings_override.as_ref().unwrap_or(&self.config);
+                                        if settings.quality_target_vmaf.is_none()
+                                            && let Some((bpp, estimate)) = item.media_info.as_ref().and_then(|i| encode::estimate_quality(settings, i, item.trim))
+                                        {
+                                            ui.label("Expected quality:");
+                                            ui.label(egui::RichText::new(format!("{} ({:.3} bits per pixel)", estimate.label(), bpp)).color(quality_color(ui, estimate)));
+                                            ui.end_row();
+                                        }
+
+                                        ui.label("Settings:");
+                                        match &item.settings_override {
+                                            Some(settings) => ui.label(format!("{} (own snapshot)", settings.summary())),
+                                            None => ui.label(self.config.summary()),
+                                        };
+                                        ui.end_row();
+
+                                        if let Some(leftover) = &item.leftover_output {
+                                            ui.label("Leftover:");
+                                            ui.horizontal(|ui| {
+                                                ui.label(format!("Unfinished {}", leftover.file_name().unwrap_or_default().to_string_lossy()));
+                                                if ui.button("Delete").clicked() {
+                                                    confirmation = Some(Confirmation::DeleteLeftover(item.id, leftover.clone()));
+                                                }
+                                                if ui.button("Keep")
+                                                    .on_hover_text("Renames it to .partial so the next encode doesn't overwrite it")
+                                                    .clicked()
+                                                {
+                                                    resolve_leftover = Some((item.id, true));
+                                                }
+                                            });
+                                            ui.end_row();
+                                        }
+                                    });
+
+                                    ui.horizontal(|ui| {
+                                        if matches!(item.status, FileStatus::Done | FileStatus::AlreadyOptimal | FileStatus::Failed(_)) {
+                                            ui.menu_button("Compress Again", |ui| {
+                                                if ui.button("From original source").clicked() {
+                                                    compress_again = Some((item.id, false));
+                                                    ui.close_menu();
+                                                }
+                                                let has_output = item.output_size_bytes.is_some();
+                                                if ui.add_enabled(has_output, egui::Button::new("From compressed output"))
+                                                    .on_hover_text("Use the previous output as the new source")
+                                                    .clicked()
+                                                {
+                                                    compress_again = Some((item.id, true));
+                                                    ui.close_menu();
+                                                }
+                                            });
+                                        } else if item.status == FileStatus::Waiting {
+                                            ui.menu_button("Priority", |ui| {
+                                                if ui.button("Do this next").clicked() {
+                                                    prioritize = Some((item.id, false));
+                                                    ui.close_menu();
+                                                }
+                                                if ui.button("Do this now")
+                                                    .on_hover_text("Interrupts the running file, which restarts afterwards")
+                                                    .clicked()
+                                                {
+                                                    prioritize = Some((item.id, true));
+                                                    ui.close_menu();
+                                                }
+                                            });
+                                        }
+                                        if item.status == FileStatus::Done
+                                            && item.output_size_bytes.is_some()
+                                            && ui.add_enabled(!self.contact_sheet_running.load(Ordering::SeqCst), egui::Button::new("Contact Sheet"))
+                                                .on_hover_text("Save a grid of frames from the output next to it and show it")
+                                                .clicked()
+                                        {
+                                            contact_sheet = Some(item.id);
+                                        }
+                                        if item.status == FileStatus::Waiting
+                                            && item.image_sequence.is_none()
+                                            && !self.ffmpeg_busy.load(Ordering::SeqCst)
+                                            && ui.button("Append to Output...")
+                                                .on_hover_text("Encode this file to match an earlier output and add it to the end of that file")
+                                                .clicked()
+                                        {
+                                            append = Some(item.id);
+                                        }
+                                        // sequences go through their own command, only one that already ran can be copied
+                                        if (item.ffmpeg_command.is_some() || item.image_sequence.is_none())
+                                            && ui.button("Copy ffmpeg Command")
+                                                .on_hover_text(if item.ffmpeg_command.is_some() {
+                                                    "Copy the command that ran for this file to the clipboard"
+                                                } else {
+                                                    "Copy the command that will run for this file to the clipboard"
+                                                })
+                                                .clicked()
+                                        {
+                                            copy_command = Some(item.id);
+                                        }
+                                        if self.item_logs.lock().unwrap().contains_key(&item.id)
+                                            && ui.button("View Log").on_hover_text("Show the ffmpeg output of this file's last run").clicked()
+                                        {
+                                            view_log = Some(item.id);
+                                        }
+                                        if item.status != FileStatus::Processing
+                                            && ui.button("Re-probe").on_hover_text("Read the file's size and details again").clicked()
+                                        {
+                                            reprobe = Some(item.id);
+                                        }
+                                    });
+                                });
+                        }
+
+                        if let Some((item_id, from_output)) = compress_again {
+                            self.compress_again(item_id, from_output);
+                        }
+                        if let Some((item_id, interrupt)) = prioritize {
+                            self.prioritize(item_id, interrupt);
+                        }
+                        if let Some((item_id, keep)) = resolve_leftover {
+                            self.resolve_leftover(item_id, keep);
+                        }
+                        if let Some(item_id) = reprobe {
+                            self.reprobe(item_id);
+                        }
+                        if let Some(item_id) = append {
+                            self.start_append(item_id);
+                        }
+                        if let Some(item_id) = contact_sheet {
+                            self.create_contact_sheet(item_id);
+                        }
+                        if confirmation.is_some() {
+                            self.pending_confirmation = confirmation;
+                        }
+                        if view_log.is_some() {
+                            self.log_window_item = view_log;
+                        }
+                        if let Some(item_id) = copy_command {
+                            self.copy_ffmpeg_command(item_id, ui.ctx());
+                        }
+                        if let Some((item_id, trim)) = edited_trim
+                            && let Ok(mut queue) = self.video_queue.lock()
+                            && let Some(item) = queue.iter_mut().find(|i| i.id == item_id)
+                        {
+                            item.trim = trim;
+                        }
+                        for (item_id, path, duration) in thumbnail_requests {
+                            self.request_thumbnails(item_id, path, duration);
+                        }
+                        if let Some((item_id, stream)) = edited_video_stream
+                            && let Ok(mut queue) = self.video_queue.lock()
+                            && let Some(item) = queue.iter_mut().find(|i| i.id == item_id)
+                        {
+                            item.video_stream = Some(stream);
+                        }
+                        if let Some((item_id, content_type)) = edited_content_type
+                            && let Ok(mut queue) = self.video_queue.lock()
+                            && let Some(item) = queue.iter_mut().find(|i| i.id == item_id)
+                        {
+                            item.content_type = Some(content_type);
+                        }
+                        if let Some((item_id, label)) = edited_label
+                            && let Ok(mut queue) = self.video_queue.lock()
+                            && let Some(item) = queue.iter_mut().find(|i| i.id == item_id)
+                        {
+                            item.label = label;
+                        }
+
+                        self.output_estimate_footer(ui, &queue);
+                    }
+                }
+
+                Tab::Options => {
+                    ui.label(egui::RichText::new("Compression").strong());
+
+                    ui.horizontal(|ui| {
+                        let label_id = ui.label("Target size:").id;
+                        if ui.add(egui::DragValue::new(&mut self.config.target_size_mb)).labelled_by(label_id).changed() {
+                            self.config_dirty = true;
+                        }
+                        egui::ComboBox::from_id_salt("size_unit_combo")
+                            .selected_text(self.config.size_unit.label())
+                            .show_ui(ui, |ui| {
+                                for (unit, label) in [(SizeUnit::Decimal, "MB (1,000,000 bytes)"), (SizeUnit::Binary, "MiB (1,048,576 bytes)")] {
+                                    ui.selectable_value(&mut self.config.size_unit, unit, label)
+                                        .changed().then(|| {
+                                            self.config_dirty = true;
+                                        });
+                                }
+                            }).response.labelled_by(label_id).on_hover_text("Discord and Windows count sizes in MiB, macOS and most websites in MB");
+
+                        let unit = self.config.size_unit;
+                        let matching_service = ServiceTarget::ALL.into_iter()
+                            .find(|service| service.target_size(unit) == self.config.target_size_mb);
+                        egui::ComboBox::from_id_salt("service_target_combo")
+                            .selected_text(matching_service.map_or("For service...", |service| service.name()))
+                            .show_ui(ui, |ui| {
+                                for service in ServiceTarget::ALL {
+                                    let (limit, limit_unit) = service.limit();
+                                    if ui.selectable_label(matching_service == Some(service), format!("{} ({} {} limit)", service.name(), limit, limit_unit.label())).clicked() {
+                                        self.config.target_size_mb = service.target_size(unit);
+                                        self.config_dirty = true;
+                                    }
+                                }
+                            }).response.on_hover_text("Sets the target size a little under the service's upload limit");
+
+                        // judged on the next file that will be compressed
+                        let next_item = {
+                            let queue = self.video_queue.lock().unwrap();
+                            let mut probed_waiting = queue.iter().filter(|i| i.status == FileStatus::Waiting && i.media_info.is_some());
+                            if self.config.newest_first { probed_waiting.next_back() } else { probed_waiting.next() }.cloned()
+                        };
+                        if self.config.quality_target_vmaf.is_none()
+                            && let Some(item) = next_item
+                            && let Some(info) = &item.media_info
+                            && let Some((bpp, estimate)) = encode::estimate_quality(&self.config, info, item.trim)
+                        {
+                            ui.label(egui::RichText::new(format!("{} quality", estimate.label())).color(quality_color(ui, estimate)))
+                                .on_hover_text(format!(
+                                    "{:.3} bits per pixel per frame for {}",
+                                    bpp,
+                                    item.path.file_name().unwrap_or_default().to_string_lossy(),
+                                ));
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        let label_id = ui.label("Auto target:").id;
+                        let mut auto_target = self.config.quality_target_vmaf.is_some();
+                        if ui.checkbox(&mut auto_target, "Smallest size with a VMAF of at least").changed() {
+                            self.config.quality_target_vmaf = auto_target.then_some(93.0);
+                            self.config_dirty = true;
+                        }
+                        if let Some(vmaf) = &mut self.config.quality_target_vmaf
+                            && ui.add(egui::DragValue::new(vmaf).range(50.0..=100.0).speed(0.1)).labelled_by(label_id).changed()
+                        {
+                            self.config_dirty = true;
+                        }
+                    }).response.on_hover_text(
+                        "Replaces the target size. Runs several sample encodes per file to find the bitrate, \
+                        which takes much longer. Needs an ffmpeg build with libvmaf"
+                    );
+
+                    ui.horizontal(|ui| {
+                        ui.label("Size accuracy:");
+                        if self.config.size_correction_samples == 0 {
+                            ui.label(egui::RichText::new("No encodes measured yet").weak());
+                        } else {
+                            let overshoot = (self.config.size_correction - 1.0) * 100.0;
+                            ui.label(format!(
+                                "Outputs land {:.1}% {} the planned size, corrected over {} encodes",
+                                overshoot.abs(),
+                                if overshoot >= 0.0 { "over" } else { "under" },
+                                self.config.size_correction_samples,
+                            ));
+                            if ui.small_button("Reset").clicked() {
+                                self.config.size_correction = 1.0;
+                                self.config.size_correction_samples = 0;
+                                self.config_dirty = true;
+                            }
+                        }
+                    }).response.on_hover_text("Each finished encode compares its size to the plan and nudges the bitrate of later encodes to hit the target more precisely");
+
+                    ui.horizontal(|ui| {
+                        let label_id = ui.label("Frame rate:").id;
+                        let rates = [(None, "Same as source"), (Some(23.976), "23.976"), (Some(24.0), "24"), (Some(25.0), "25"), (Some(29.97), "29.97"), (Some(30.0), "30"), (Some(50.0), "50"), (Some(59.94), "59.94"), (Some(60.0), "60")];
+                        let selected_label = match self.config.frame_rate {
+                            Some(fps) => format!("{} fps", fps),
+                            None => "Same as source".to_string(),
+                        };
+                        egui::ComboBox::from_id_salt("frame_rate_combo")
+                            .selected_text(selected_label)
+                            .show_ui(ui, |ui| {
+                                for (rate, label) in rates {
+                                    ui.selectable_value(&mut self.config.frame_rate, rate, label)
+                                        .changed().then(|| {
+                                            self.config_dirty = true;
+                                        });
+                                }
+                            }).response.labelled_by(label_id);
+                        // any other rate can be typed in
+                        if let Some(fps) = &mut self.config.frame_rate {
+                            if ui.add(egui::DragValue::new(fps).range(1.0..=240.0).speed(0.1).max_decimals(3).suffix(" fps")).labelled_by(label_id).changed() {
+                                self.config_dirty = true;
+                            }
+                            ui.checkbox(&mut self.config.interpolate_frames, "Interpolate when raising")
+                                .on_hover_text("Makes up the extra frames from the motion around them (minterpolate) instead of repeating frames, \
+                                    for smoother motion. Only used when the source's frame rate is lower. Very slow, often slower than real time")
+                                .changed().then(|| {
+                                    self.config_dirty = true;
+                                });
+                        }
+                    }).response.on_hover_text("Same as source keeps every frame. NTSC rates like 29.97 are passed to ffmpeg as exact fractions");
+
+                    ui.horizontal(|ui| {
+                        let label_id = ui.label("Image sequence frame rate:").id;
+                        if ui.add(egui::DragValue::new(&mut self.config.sequence_fps).range(1..=120).suffix(" fps")).labelled_by(label_id).changed() {
+                            self.config_dirty = true;
+                        }
+                    }).response.on_hover_text("How many images make up one second of video, applies to sequences added afterwards");
+
+                    ui.horizontal(|ui| {
+                        ui.label("Low bitrate:");
+                        ui.checkbox(&mut self.config.auto_reduce_fps, "Auto-reduce frame rate when needed")
+                            .on_hover_ui(|ui| {
+                                ui.label("Halves high frame rates (e.g. 60 to 30) when the target size leaves too little bitrate, unless a frame rate is set");
+                            }).changed().then(|| {
+                                self.config_dirty = true;
+                            });
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Encoder:");
+                        ui.selectable_value(&mut self.config.encoder, Encoder::CpuX264, "CPU")
+                            .changed().then(|| {
+                                self.config_dirty = true;
+                            });
+                        ui.selectable_value(&mut self.config.encoder, Encoder::GpuNvenc, "GPU")
+                            .on_hover_ui(|ui| {
+                                ui.label("Faster than CPU, but produces larger file size");
+                            }).changed().then(|| {
+                                self.config_dirty = true;
+                            });
+                    });
+
+                    if self.config.encoder == Encoder::GpuNvenc {
+                        ui.horizontal(|ui| {
+                            let label_id = ui.label("NVENC session limit:").id;
+                            if ui.add(egui::DragValue::new(&mut self.config.nvenc_session_limit).range(1..=32)).labelled_by(label_id).changed() {
+                                self.config_dirty = true;
+                            }
+                        }).response.on_hover_text("Consumer drivers only allow a few encode sessions at once, including ones used by other programs. Jobs wait while the limit is reached.");
+                    }
+
+                    ui.horizontal(|ui| {
+                        ui.label("Bitrate mode:");
+                        let modes = [
+                            (BitrateMode::Abr, "Bitrate varies around the target, spending more on complex scenes"),
+                            (BitrateMode::TwoPassAbr, "Analyzes the whole video first, then spends the bitrate where it's needed. Best quality for the size, but takes about twice as long on the CPU encoder"),
+                            (BitrateMode::Cbr, "Predictable bitrate for strict players and streaming, but lower quality at the same size"),
+                            (BitrateMode::Crf, "Keeps a fixed quality level and ignores the target size, so the output can end up any size"),
+                        ];
+                        for (mode, description) in modes {
+                            ui.selectable_value(&mut self.config.bitrate_mode, mode, mode.label())
+                                .on_hover_text(description)
+                                .changed().then(|| {
+                                    self.config_dirty = true;
+                                });
+                        }
+                    });
+
+                    if self.config.bitrate_mode.size_targeted() {
+                        ui.horizontal(|ui| {
+                            let label_id = ui.label("Bitrate:").id;
+                            let mut manual = self.config.manual_video_bitrate_kbps.is_some();
+                            if ui.checkbox(&mut manual, "Set manually, video").changed() {
+                                self.config.manual_video_bitrate_kbps = manual.then_some(2500);
+                                self.config.manual_audio_bitrate_kbps = None;
+                                self.config_dirty = true;
+                            }
+                            if let Some(video_kbps) = &mut self.config.manual_video_bitrate_kbps {
+                                if ui.add(egui::DragValue::new(video_kbps).range(50..=100_000).speed(10).suffix(" kbps")).labelled_by(label_id).changed() {
+                                    self.config_dirty = true;
+                                }
+                                let mut set_audio = self.config.manual_audio_bitrate_kbps.is_some();
+                                if ui.checkbox(&mut set_audio, "audio").changed() {
+                                    self.config.manual_audio_bitrate_kbps = set_audio.then_some(128);
+                                    self.config_dirty = true;
+                                }
+                                if let Some(audio_kbps) = &mut self.config.manual_audio_bitrate_kbps
+                                    && ui.add(egui::DragValue::new(audio_kbps).range(32..=512).suffix(" kbps")).labelled_by(label_id).changed()
+                                {
+                                    self.config_dirty = true;
+                                }
+                            }
+                        }).response.on_hover_text(
+                            "Passes these bitrates to ffmpeg as they are, the target size and auto target are ignored. \
+                            Audio follows the source unless it's set here, audio quality and copying still take precedence"
+                        );
+                    } else {
+                        ui.horizontal(|ui| {
+                            let label_id = ui.label("Quality level:").id;
+                            if ui.add(egui::Slider::new(&mut self.config.quality_level, 0..=51)).labelled_by(label_id).changed() {
+                                self.config_dirty = true;
+                            }
+                        }).response.on_hover_text("Lower is better and larger, 18-28 is the usual range. Maps to -crf on the CPU encoder and -cq on the GPU encoder");
+                    }
+
+                    ui.horizontal(|ui| {
+                        ui.label("Bit depth:");
+                        ui.selectable_value(&mut self.config.bit_depth, BitDepth::Eight, "8-bit")
+                            .changed().then(|| {
+                                self.config_dirty = true;
+                            });
+                        ui.add_enabled_ui(self.config.encoder.supports(BitDepth::Ten), |ui| {
+                            ui.selectable_value(&mut self.config.bit_depth, BitDepth::Ten, "10-bit")
+                                .on_hover_ui(|ui| {
+                                    ui.label("Less banding in gradients at low bitrates, but many phones, browsers and TVs can't play 10-bit H.264");
+                                })
+                                .on_disabled_hover_text("The GPU encoder can't write 10-bit H.264")
+                                .changed().then(|| {
+                                    self.config_dirty = true;
+                                });
+                        });
+                        if self.config.bit_depth == BitDepth::Ten {
+                            if self.config.encoder.supports(BitDepth::Ten) {
+                                ui.label(egui::RichText::new("Limited player support").color(ui.visuals().warn_fg_color));
+                            } else {
+                                ui.label(egui::RichText::new("Not supported by the GPU encoder, 8-bit is used").color(ui.visuals().warn_fg_color));
+                            }
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Resolution:");
+                        let current = &mut self.config.resolution;
+
+                        let options = [
+                            (None, "Original"),
+                            (Some(Resolution::R1080), "1080p"),
+                            (Some(Resolution::R720), "720p"),
+                            (Some(Resolution::R480), "480p"),
+                        ];
+
+                        for (val, label) in options {
+                            if ui
+                                .selectable_label(current == &val, label)
+                                .clicked()
+                            {
+                                *current = val.clone();
+                                self.config_dirty = true;
+                            }
+                        }
+
+                        let is_percent = matches!(current, Some(Resolution::Percent(_)));
+                        if ui.selectable_label(is_percent, "Scale").on_hover_text("A percentage of each source's size, never upscales a mixed batch").clicked() && !is_percent {
+                            *current = Some(Resolution::Percent(50));
+                            self.config_dirty = true;
+                        }
+                        if let Some(Resolution::Percent(percent)) = current
+                            && ui.add(egui::DragValue::new(percent).range(1..=100).suffix("%")).changed()
+                        {
+                            self.config_dirty = true;
+                        }
+                        if matches!(current, Some(Resolution::R1080 | Resolution::R720 | Resolution::R480))
+                            && ui.checkbox(&mut self.config.no_upscale, "Don't upscale")
+                                .on_hover_text("Sources already at or below this height keep their size instead of being enlarged")
+                                .changed()
+                        {
+                            self.config_dirty = true;
+                        }
+                        if let Some((width, height)) = self.config.resolution.as_ref().and_then(|r| r.canvas_size())
+                            && ui.checkbox(&mut self.config.pad_to_fit, "Fit with padding")
+                                .on_hover_text(format!("Every output is exactly {}x{}. The picture is scaled to fit inside and the rest is filled with black bars, for sites that demand exact dimensions", width, height))
+                                .changed()
+                        {
+                            self.config_dirty = true;
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        let label_id = ui.label("Scaling algorithm:").id;
+
+                        let algorithms = [
+                            (ScaleAlgorithm::None, "Default"),
+                            (ScaleAlgorithm::Bilinear, "bilinear"),
+                            (ScaleAlgorithm::Bicubic, "bicubic"),
+                            (ScaleAlgorithm::Lanczos, "lanczos"),
+                            (ScaleAlgorithm::Spline, "spline"),
+                        ];
+                        let selected_label = algorithms.iter()
+                            .find(|(a, _)| *a == self.config.scale_algorithm)
+                            .map_or("Default", |(_, label)| label);
+                        egui::ComboBox::from_id_salt("scale_algorithm_combo")
+                            .selected_text(selected_label)
+                            .show_ui(ui, |ui| {
+                                for (algorithm, label) in algorithms {
+                                    ui.selectable_value(&mut self.config.scale_algorithm, algorithm, label)
+                                        .changed().then(|| {
+                                            self.config_dirty = true;
+                                        });
+                                }
+                            }).response.labelled_by(label_id);
+                    }).response.on_hover_text("lanczos and spline are sharper when downscaling, bilinear is fastest");
+
+                    ui.horizontal(|ui| {
+                        let label_id = ui.label("Crop:").id;
+                        let mut crop_enabled = self.config.crop.is_some();
+                        if ui.checkbox(&mut crop_enabled, "").labelled_by(label_id).changed() {
+                            self.config.crop = crop_enabled.then(|| {
+                                let (width, height) = self.probed_frame_size();
+                                Crop::full(width, height)
+                            });
+                            self.config_dirty = true;
+                        }
+                        if let Some(crop) = &mut self.config.crop {
+                            // an empty rectangle is the only crop ffmpeg can't shrink to fit
+                            let fields = [
+                                ("W", &mut crop.width, 2),
+                                ("H", &mut crop.height, 2),
+                                ("X", &mut crop.x, 0),
+                                ("Y", &mut crop.y, 0),
+                            ];
+                            for (label, value, min) in fields {
+                                let label_id = ui.label(label).id;
+                                if ui.add(egui::DragValue::new(value).range(min..=u32::MAX)).labelled_by(label_id).changed() {
+                                    self.config_dirty = true;
+                                }
+                            }
+                        }
+                    }).response.on_hover_text("Crop region in source pixels, applied before resizing");
+
+                    ui.horizontal(|ui| {
+                        ui.label("Subtitles:");
+                        let mut burn = self.config.burn_subtitles.is_some();
+                        if ui.checkbox(&mut burn, "Burn in").changed() {
+                            self.config.burn_subtitles = burn.then_some(SubtitleSource::Embedded(0));
+                            self.config_dirty = true;
+                        }
+                        match &mut self.config.burn_subtitles {
+                            Some(SubtitleSource::Embedded(index)) => {
+                                let label_id = ui.label("track").id;
+                                if ui.add(egui::DragValue::new(index)).labelled_by(label_id).on_hover_text("Counts subtitle tracks only, starting at 0").changed() {
+                                    self.config_dirty = true;
+                                }
+                            }
+                            Some(SubtitleSource::External(path)) => {
+                                ui.label(path.file_name().unwrap_or_default().to_string_lossy());
+                                if ui.small_button("Use embedded").clicked() {
+                                    self.config.burn_subtitles = Some(SubtitleSource::Embedded(0));
+                                    self.config_dirty = true;
+                                }
+                            }
+                            None => {}
+                        }
+                        if self.config.burn_subtitles.is_some()
+                            && ui.button("Choose File...").clicked()
+                            && let Some(path) = rfd::FileDialog::new()
+                                .add_filter("Subtitles", &["srt", "ass", "ssa", "vtt"])
+                                .pick_file()
+                        {
+                            self.config.burn_subtitles = Some(SubtitleSource::External(path));
+                            self.config_dirty = true;
+                        }
+                    }).response.on_hover_text("Draws the subtitles into the picture, for players and sites that drop subtitle tracks");
+                    if self.config.burn_subtitles.is_some() {
+                        ui.label(egui::RichText::new("Burned-in subtitles can't be turned off or changed later").color(ui.visuals().warn_fg_color));
+                    }
+
+                    ui.horizontal(|ui| {
+                        let label_id = ui.label("Preset:").id;
+
+                        egui::ComboBox::from_id_salt("preset_combo")
+                            .selected_text(match self.config.preset {
+                                Preset::None => "Unspecified",
+                                ref p => p.as_str().unwrap_or("Unknown"),
+                            })
+                            .show_ui(ui, |ui| {
+                                let presets = [
+                                    Preset::None,
+                                    Preset::Ultrafast,
+                                    Preset::Superfast,
+                                    Preset::Veryfast,
+                                    Preset::Faster,
+                                    Preset::Fast,
+                                    Preset::Medium,
+                                    Preset::Slow,
+                                    Preset::Slower,
+                                    Preset::Veryslow,
+                                ];
+                                for preset in presets {
+                                    let label = preset.as_str().unwrap_or("Unspecified");
+                                    ui.selectable_value(&mut self.config.preset, preset, label)
+                                        .changed().then(|| {
+                                            self.config_dirty = true;
+                                        });
+                                }
+                            }).response.labelled_by(label_id);
+                    });
+
+                    ui.horizontal(|ui| {
+                        let label_id = ui.label("Content:").id;
+                        egui::ComboBox::from_id_salt("content_type_combo")
+                            .selected_text(self.config.content_type.label())
+                            .show_ui(ui, |ui| {
+                                for content_type in ContentType::ALL {
+                                    ui.selectable_value(&mut self.config.content_type, content_type, content_type.label())
+                                        .on_hover_text(content_type.description())
+                                        .changed().then(|| {
+                                            self.config_dirty = true;
+                                        });
+                                }
+                            }).response.labelled_by(label_id);
+                    }).response.on_hover_text("Tunes the encoder for what the videos show. Single files can be changed in their details in the queue");
+
+                    ui.horizontal(|ui| {
+                        ui.label("Color:");
+                        ui.checkbox(&mut self.config.preserve_color_tags, "Keep source color tags")
+                            .on_hover_ui(|ui| {
+                                ui.label("Copies the color range, matrix, primaries and transfer so colors don't look washed out or shifted after compression");
+                            }).changed().then(|| {
+                                self.config_dirty = true;
+                            });
+                        let label_id = ui.label("Range:").id;
+                        let ranges = [(None, "Same as source"), (Some(ColorRange::Limited), "Limited"), (Some(ColorRange::Full), "Full")];
+                        let selected_label = ranges.iter()
+                            .find(|(r, _)| *r == self.config.color_range)
+                            .map_or("Same as source", |(_, label)| label);
+                        egui::ComboBox::from_id_salt("color_range_combo")
+                            .selected_text(selected_label)
+                            .show_ui(ui, |ui| {
+                                for (range, label) in ranges {
+                                    ui.selectable_value(&mut self.config.color_range, range, label)
+                                        .changed().then(|| {
+                                            self.config_dirty = true;
+                                        });
+                                }
+                            }).response.labelled_by(label_id)
+                            .on_hover_text("Overrides the range tag for sources that are tagged wrong. Try Full if the output looks washed out, Limited if blacks look crushed");
+                    });
+
+                    ui.horizontal(|ui| {
+                        let label_id = ui.label("Profile:").id;
+                        // the 10-bit option already picks high10
+                        let ten_bit = self.config.bit_depth == BitDepth::Ten && self.config.encoder.supports(BitDepth::Ten);
+                        ui.add_enabled_ui(!ten_bit, |ui| {
+                            egui::ComboBox::from_id_salt("profile_combo")
+                                .selected_text(self.config.profile.map_or("Encoder default", |p| p.as_str()))
+                                .show_ui(ui, |ui| {
+                                    for profile in [None, Some(Profile::Baseline), Some(Profile::Main), Some(Profile::High)] {
+                                        let label = profile.map_or("Encoder default", |p| p.as_str());
+                                        ui.selectable_value(&mut self.config.profile, profile, label)
+                                            .changed().then(|| {
+                                                self.config_dirty = true;
+                                            });
+                                    }
+                                }).response.labelled_by(label_id);
+                        }).response.on_disabled_hover_text("10-bit output always uses the high10 profile");
+                        let label_id = ui.label("Level:").id;
+                        egui::ComboBox::from_id_salt("level_combo")
+                            .selected_text(self.config.level.as_deref().unwrap_or("Encoder default"))
+                            .show_ui(ui, |ui| {
+                                let levels = std::iter::once(None).chain(H264_LEVELS.iter().map(|l| Some(l.to_string())));
+                                for level in levels {
+                                    let label = level.clone().unwrap_or("Encoder default".to_string());
+                                    ui.selectable_value(&mut self.config.level, level, label)
+                                        .changed().then(|| {
+                                            self.config_dirty = true;
+                                        });
+                                }
+                            }).response.labelled_by(label_id);
+                    }).response.on_hover_text("Only needed for devices that reject some videos, e.g. older TVs that require High@4.0. Baseline plays almost anywhere but compresses worst");
+
+                    ui.horizontal(|ui| {
+                        if ui.checkbox(&mut self.config.copy_audio, "Copy audio (no re-encode)").changed() {
+                            self.config_dirty = true;
+                        }
+                    }).response.on_hover_text(
+                        "Keeps the source audio exactly as it is, which suits sources that already have small AAC or Opus audio. \
+                        Its full bitrate comes out of the target size. Falls back to re-encoding when the output container can't hold it"
+                    );
+
+                    // the options below only apply when the audio is re-encoded
+                    ui.add_enabled_ui(!self.config.copy_audio, |ui| {
+                        ui.horizontal(|ui| {
+                            let label_id = ui.label("Audio sample rate:").id;
+                            let rates = [(None, "Same as source"), (Some(48_000), "48 kHz"), (Some(44_100), "44.1 kHz"), (Some(32_000), "32 kHz"), (Some(22_050), "22.05 kHz")];
+                            let selected_label = rates.iter()
+                                .find(|(r, _)| *r == self.config.audio_sample_rate)
+                                .map_or("Same as source", |(_, label)| label);
+                            egui::ComboBox::from_id_salt("audio_sample_rate_combo")
+                                .selected_text(selected_label)
+                                .show_ui(ui, |ui| {
+                                    for (rate, label) in rates {
+                                        ui.selectable_value(&mut self.config.audio_sample_rate, rate, label)
+                                            .changed().then(|| {
+                                                self.config_dirty = true;
+                                            });
+                                    }
+                                }).response.labelled_by(label_id);
+
+                            let label_id = ui.label("Channels:").id;
+                            let channels = [(None, "Same as source"), (Some(2), "Stereo"), (Some(1), "Mono")];
+                            let selected_label = channels.iter()
+                                .find(|(c, _)| *c == self.config.audio_channels)
+                                .map_or("Same as source", |(_, label)| label);
+                            egui::ComboBox::from_id_salt("audio_channels_combo")
+                                .selected_text(selected_label)
+                                .show_ui(ui, |ui| {
+                                    for (channel_count, label) in channels {
+                                        ui.selectable_value(&mut self.config.audio_channels, channel_count, label)
+                                            .changed().then(|| {
+                                                self.config_dirty = true;
+                                            });
+                                    }
+                                }).response.labelled_by(label_id);
+                        }).response.on_hover_text("Mono and lower sample rates are plenty for voice and leave more of the target size for video");
+
+                        ui.horizontal(|ui| {
+                            let label_id = ui.label("Audio quality:").id;
+                            let mut variable = self.config.audio_quality.is_some();
+                            if ui.checkbox(&mut variable, "Variable bitrate at quality").changed() {
+                                self.config.audio_quality = variable.then_some(1.0);
+                                self.config_dirty = true;
+                            }
+                            if let Some(quality) = &mut self.config.audio_quality
+                                && ui.add(egui::DragValue::new(quality).range(0.1..=2.0).speed(0.05).fixed_decimals(2)).labelled_by(label_id).changed()
+                            {
+                                self.config_dirty = true;
                             }
+                        }).response.on_hover_text(
+                            "Lets AAC spend bits where the sound needs them instead of using a fixed audio bitrate. \
+                            Higher is better, 1 is around 128 kbps for stereo. The size budget assumes the high end, so the video gets a little less"
+                        );
+                    });
+
+                    ui.horizontal(|ui| {
+                        let label_id = ui.label("Audio delay:").id;
+                        if ui.add(egui::DragValue::new(&mut self.config.audio_delay_ms).range(-5000..=5000).suffix(" ms")).labelled_by(label_id).changed() {
+                            self.config_dirty = true;
+                        }
+                        if self.config.audio_delay_ms != 0 && ui.small_button("Reset").clicked() {
+                            self.config.audio_delay_ms = 0;
+                            self.config_dirty = true;
+                        }
+                    }).response.on_hover_text("Fixes lip-sync drift: positive values play the audio later, negative values earlier");
+
+                    ui.horizontal(|ui| {
+                        let label_id = ui.label("Output name:").id;
+                        if ui.add(egui::TextEdit::singleline(&mut self.config.output_prefix).desired_width(80.0).hint_text("prefix")).labelled_by(label_id).changed() {
+                            self.config_dirty = true;
+                        }
+                        ui.label("name");
+                        if ui.add(egui::TextEdit::singleline(&mut self.config.output_suffix).desired_width(80.0).hint_text("suffix")).labelled_by(label_id).changed() {
+                            self.config_dirty = true;
+                        }
+                        ui.label(if self.config.match_source_container { ".(source)" } else { ".mp4" });
+                    }).response.on_hover_text("Characters that aren't allowed in file names are removed");
+
+                    ui.horizontal(|ui| {
+                        let title_label_id = ui.label("Title:").id;
+                        let mut set_title = self.config.metadata_title.is_some();
+                        if ui.checkbox(&mut set_title, "").labelled_by(title_label_id).changed() {
+                            self.config.metadata_title = set_title.then(String::new);
+                            self.config_dirty = true;
                         }
+                        if let Some(title) = &mut self.config.metadata_title
+                            && ui.add(egui::TextEdit::singleline(title).desired_width(160.0).hint_text("source file name")).labelled_by(title_label_id).changed()
+                        {
+                            self.config_dirty = true;
+                        }
+                        let label_id = ui.label("Comment:").id;
+                        if ui.add(egui::TextEdit::singleline(&mut self.config.metadata_comment).desired_width(160.0)).labelled_by(label_id).changed() {
+                            self.config_dirty = true;
+                        }
+                    }).response.on_hover_text("Written into the output file and shown by media players and libraries");
+
+                    ui.horizontal(|ui| {
+                        ui.label("Container:");
+                        ui.checkbox(&mut self.config.match_source_container, "Match source container")
+                            .on_hover_ui(|ui| {
+                                ui.label("mkv, mov and m4v sources keep their container, everything else becomes mp4");
+                            }).changed().then(|| {
+                                self.config_dirty = true;
+                            });
                     });
 
                     ui.horizontal(|ui| {
-                        ui.label("Preset:");
+                        ui.label("Output folder:");
+                        ui.checkbox(&mut self.config.output_to_subfolder, "Write to a \"compressed\" subfolder")
+                            .on_hover_ui(|ui| {
+                                ui.label("Creates a compressed folder next to each source file and saves the output there");
+                            }).changed().then(|| {
+                                self.config_dirty = true;
+                            });
+                    });
 
-                        egui::ComboBox::from_id_salt("preset_combo")
-                            .selected_text(match self.config.preset {
-                                Preset::None => "Unspecified",
-                                ref p => p.as_str().unwrap_or("Unknown"),
-                            })
-                            .show_ui(ui, |ui| {
-                                ui.selectable_value(&mut self.config.preset, Preset::None, "Unspecified");
-                                ui.selectable_value(&mut self.config.preset, Preset::Ultrafast, "ultrafast");
-                                ui.selectable_value(&mut self.config.preset, Preset::Superfast, "superfast");
-                                ui.selectable_value(&mut self.config.preset, Preset::Veryfast, "veryfast");
-                                ui.selectable_value(&mut self.config.preset, Preset::Faster, "faster");
-                                ui.selectable_value(&mut self.config.preset, Preset::Fast, "fast");
-                                ui.selectable_value(&mut self.config.preset, Preset::Medium, "medium");
-                                ui.selectable_value(&mut self.config.preset, Preset::Slow, "slow");
-                                ui.selectable_value(&mut self.config.preset, Preset::Slower, "slower");
-                                ui.selectable_value(&mut self.config.preset, Preset::Veryslow, "veryslow");
+                    ui.horizontal(|ui| {
+                        ui.label("Unfinished outputs:");
+                        ui.checkbox(&mut self.config.atomic_output, "Write to a .part file until the encode succeeds")
+                            .on_hover_ui(|ui| {
+                                ui.label("An interrupted encode never leaves a cut-off file under the finished name, and a failed retry keeps the previous output. Split parts are always written in place");
+                            }).changed().then(|| {
+                                self.config_dirty = true;
+                            });
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Web playback:");
+                        ui.checkbox(&mut self.config.faststart, "Optimize for web (faststart)")
+                            .on_hover_ui(|ui| {
+                                ui.label("Lets browsers start playing before the whole file has downloaded");
+                            }).changed().then(|| {
+                                self.config_dirty = true;
+                            });
+                    });
+
+                    ui.horizontal(|ui| {
+                        let label_id = ui.label("Split output:").id;
+                        let mut split_enabled = self.config.split_part_size_mb.is_some();
+                        if ui.checkbox(&mut split_enabled, "Split into parts of").changed() {
+                            self.config.split_part_size_mb = split_enabled.then_some(self.config.target_size_mb);
+                            self.config_dirty = true;
+                        }
+                        if let Some(part_size_mb) = &mut self.config.split_part_size_mb {
+                            if ui.add(egui::DragValue::new(part_size_mb).range(1..=u32::MAX)).labelled_by(label_id).changed() {
+                                self.config_dirty = true;
+                            }
+                            ui.label(self.config.size_unit.label());
+                        }
+                    }).response.on_hover_text("Target size applies to the whole video, which is cut into numbered parts under this size. Not available in Quality mode, which has no bitrate to size the parts by");
+
+                    ui.horizontal(|ui| {
+                        ui.label("Chapters:");
+                        ui.checkbox(&mut self.config.keep_chapters, "Keep chapter markers")
+                            .changed().then(|| {
+                                self.config_dirty = true;
+                            });
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Larger output:");
+                        ui.checkbox(&mut self.config.discard_larger_output, "Discard and keep original")
+                            .on_hover_ui(|ui| {
+                                ui.label("Deletes the output if it isn't smaller than the source");
+                            }).changed().then(|| {
+                                self.config_dirty = true;
                             });
+                    });
+
+                    ui.add_space(15.0);
+                    ui.collapsing("Size calculator", |ui| {
+                        ui.label("Size = bitrate × duration, so a long video needs a low bitrate to fit a small size.");
+                        let size_unit = self.config.size_unit;
+                        let calc = &mut self.calculator;
+                        ui.horizontal(|ui| {
+                            ui.label("Solve for:");
+                            ui.selectable_value(&mut calc.solve_for, CalculatorUnknown::Size, "Size");
+                            ui.selectable_value(&mut calc.solve_for, CalculatorUnknown::Duration, "Duration");
+                            ui.selectable_value(&mut calc.solve_for, CalculatorUnknown::Bitrate, "Bitrate");
+                        });
+
+                        match calc.solve_for {
+                            CalculatorUnknown::Size => {
+                                calc.size_mb = encode::size_for_bitrate(calc.bitrate_kbps * 1000.0, calc.duration_secs, size_unit);
+                            }
+                            CalculatorUnknown::Duration => {
+                                calc.duration_secs = encode::duration_for_size(calc.size_mb, (calc.bitrate_kbps * 1000.0).max(1.0), size_unit);
+                            }
+                            CalculatorUnknown::Bitrate => {
+                                calc.bitrate_kbps = encode::bitrate_for_size(calc.size_mb, calc.duration_secs.max(1.0), size_unit) / 1000.0;
+                            }
+                        }
+
+                        egui::Grid::new("calculator_grid").show(ui, |ui| {
+                            let label_id = ui.label(format!("Size ({}):", size_unit.label())).id;
+                            ui.add_enabled(
+                                calc.solve_for != CalculatorUnknown::Size,
+                                egui::DragValue::new(&mut calc.size_mb).range(0.0..=f64::MAX).max_decimals(1),
+                            ).labelled_by(label_id);
+                            ui.end_row();
+
+                            let label_id = ui.label("Duration:").id;
+                            ui.add_enabled(
+                                calc.solve_for != CalculatorUnknown::Duration,
+                                egui::DragValue::new(&mut calc.duration_secs)
+                                    .range(1.0..=f64::MAX)
+                                    .custom_formatter(|secs, _| utils::format_duration(secs))
+                                    .custom_parser(utils::parse_timestamp),
+                            ).labelled_by(label_id);
+                            ui.end_row();
 
-                        self.config_dirty = true;
+                            let label_id = ui.label("Total bitrate (kbps):").id;
+                            ui.add_enabled(
+                                calc.solve_for != CalculatorUnknown::Bitrate,
+                                egui::DragValue::new(&mut calc.bitrate_kbps).range(0.0..=f64::MAX).max_decimals(0),
+                            ).labelled_by(label_id);
+                            ui.end_row();
+                        });
                     });
 
                     ui.add_space(15.0);
                     ui.horizontal(|ui| {
                         if ui.button("Reset to Defaults").clicked() {
-                            self.reset_config_to_default();
+                            let changes = self.reset_changes();
+                            if changes.is_empty() {
+                                self.settings_io_message = Some("Settings are already at their defaults.".to_string());
+                            } else {
+                                self.pending_confirmation = Some(Confirmation::ResetDefaults(changes));
+                            }
                         }
                         
-                        if ui.button("Open Config Folder").clicked() {
-                            if let Ok(path) = confy::get_configuration_file_path(PROGRAM_CONFIG_NAME, None) {
-                                if let Some(folder) = path.parent() {
-                                    #[cfg(target_os = "windows")]
-                                    {
-                                        let _ = std::process::Command::new("explorer")
-                                            .arg(folder)
-                                            .spawn();
-                                    }
-                                    #[cfg(target_os = "macos")]
-                                    {
-                                        let _ = std::process::Command::new("open")
-                                            .arg(folder)
-                                            .spawn();
-                                    }
-                                    #[cfg(target_os = "linux")]
-                                    {
-                                        let _ = std::process::Command::new("xdg-open")
-                                            .arg(folder)
-                                            .spawn();
-                                    }
-                                }
-                            }
+                        let config_path = confy::get_configuration_file_path(PROGRAM_CONFIG_NAME, None).ok();
+                        let config_folder = config_path.as_ref().and_then(|p| p.parent());
+                        let open_button = ui.add_enabled(config_folder.is_some(), egui::Button::new("Open Config Folder"));
+                        let open_button = match config_folder {
+                            Some(folder) => open_button.on_hover_text(folder.display().to_string()),
+                            None => open_button,
+                        };
+                        if open_button.clicked()
+                            && let Some(folder) = config_folder
+                            && let Err(err) = open::that(folder)
+                        {
+                            self.settings_io_message = Some(format!("Couldn't open {}: {}", folder.display(), err));
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        if ui.button("Export Settings").clicked() {
+                            self.export_settings();
+                        }
+                        if ui.button("Import Settings").clicked() {
+                            self.import_settings();
+                            self.apply_theme(ctx);
                         }
                     });
+                    if let Some(message) = &self.settings_io_message {
+                        ui.label(egui::RichText::new(message).weak());
+                    }
 
                     ui.add_space(15.0);
                     ui.label(egui::RichText::new("Program").strong());
+
+                    ui.horizontal(|ui| {
+                        ui.label("ffmpeg log level:");
+                        let levels = [
+                            (LogLevel::Quiet, "Quiet"),
+                            (LogLevel::Error, "Errors"),
+                            (LogLevel::Warning, "Warnings"),
+                            (LogLevel::Info, "Info"),
+                        ];
+                        for (level, label) in levels {
+                            ui.selectable_value(&mut self.config.log_level, level, label)
+                                .changed().then(|| {
+                                    self.config_dirty = true;
+                                });
+                        }
+                    }).response.on_hover_text("How much ffmpeg output is kept in the Debug Output tab");
                     
+                    ui.horizontal(|ui| {
+                        ui.label("Diagnostics:");
+                        let running = self.self_test_running.load(Ordering::SeqCst);
+                        if ui.add_enabled(!running, egui::Button::new("Test Encoder"))
+                            .on_hover_text("Encodes a short generated test pattern with the selected encoder and settings")
+                            .clicked()
+                        {
+                            self.start_self_test();
+                        }
+                        if running {
+                            ui.spinner();
+                        } else if let Some(message) = self.self_test_message.lock().unwrap().as_ref() {
+                            ui.label(message);
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Priority:");
+                        ui.checkbox(&mut self.config.low_priority, "Run in background priority")
+                            .on_hover_ui(|ui| {
+                                ui.label("Encodes take longer while other programs are busy, but the computer stays responsive. Applies to the next file");
+                            }).changed().then(|| {
+                                self.config_dirty = true;
+                            });
+                    });
+
+                    ui.collapsing("Advanced", |ui| {
+                        let core_count = utils::cpu_core_count();
+                        ui.horizontal(|ui| {
+                            let label_id = ui.label("CPU cores:").id;
+                            let mut limited = self.config.cpu_affinity.is_some();
+                            if ui.checkbox(&mut limited, "Limit ffmpeg to the first").changed() {
+                                self.config.cpu_affinity = limited.then(|| utils::first_cores_mask((core_count / 2).max(1)));
+                                self.config_dirty = true;
+                            }
+                            if let Some(mask) = self.config.cpu_affinity {
+                                let mut count = mask.count_ones().min(core_count);
+                                if ui.add(egui::DragValue::new(&mut count).range(1..=core_count).suffix(" cores")).labelled_by(label_id).changed() {
+                                    self.config.cpu_affinity = Some(utils::first_cores_mask(count));
+                                    self.config_dirty = true;
+                                }
+                            }
+                        }).response.on_hover_text("Leaves the other cores free for games or other work while encoding. Only works on Linux and Windows. Applies to the next file");
+
+                        // picking single cores covers layouts the first-N count can't, like skipping efficiency cores
+                        if let Some(mask) = &mut self.config.cpu_affinity {
+                            ui.horizontal_wrapped(|ui| {
+                                ui.label("Cores:");
+                                for core in 0..core_count {
+                                    let bit = 1u64 << core;
+                                    let mut enabled = *mask & bit != 0;
+                                    // at least one core has to stay on
+                                    let can_toggle = !enabled || mask.count_ones() > 1;
+                                    if ui.add_enabled(can_toggle, egui::Checkbox::new(&mut enabled, core.to_string())).changed() {
+                                        *mask ^= bit;
+                                        self.config_dirty = true;
+                                    }
+                                }
+                            });
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        let label_id = ui.label("Stall watchdog:").id;
+                        let mut watchdog_enabled = self.config.stall_timeout_secs.is_some();
+                        if ui.checkbox(&mut watchdog_enabled, "Stop encodes with no progress for").changed() {
+                            self.config.stall_timeout_secs = watchdog_enabled.then_some(120);
+                            self.config_dirty = true;
+                        }
+                        if let Some(timeout_secs) = &mut self.config.stall_timeout_secs {
+                            if ui.add(egui::DragValue::new(timeout_secs).range(10..=3600)).labelled_by(label_id).changed() {
+                                self.config_dirty = true;
+                            }
+                            ui.label("seconds");
+                        }
+                    }).response.on_hover_text("A hung GPU driver can leave ffmpeg running without making progress. Stalled files are marked failed and the queue moves on");
+
+                    ui.horizontal(|ui| {
+                        let label_id = ui.label("Progress updates:").id;
+                        if ui.add(egui::DragValue::new(&mut self.config.ui_refresh_hz).range(1..=30).suffix(" per second")).labelled_by(label_id).changed() {
+                            self.config_dirty = true;
+                        }
+                    }).response.on_hover_text("How often progress and log lines from a running encode are shown. Lower is smoother on slow machines. Applies to the next file");
+
+                    ui.horizontal(|ui| {
+                        ui.label("Temp folder:");
+                        match &self.config.temp_dir {
+                            Some(dir) => ui.label(dir.display().to_string()),
+                            None => ui.label(egui::RichText::new(format!("System default ({})", std::env::temp_dir().display())).weak()),
+                        };
+                        if ui.button("Browse...").clicked()
+                            && let Some(dir) = rfd::FileDialog::new().pick_folder()
+                        {
+                            self.config.temp_dir = Some(dir);
+                            self.config_dirty = true;
+                        }
+                        if self.config.temp_dir.is_some() && ui.button("Use Default").clicked() {
+                            self.config.temp_dir = None;
+                            self.config_dirty = true;
+                        }
+                    }).response.on_hover_text("Quality search samples and append segments are written here and deleted afterwards. Pick a folder on a larger drive if the system drive is small");
+
+                    ui.horizontal(|ui| {
+                        ui.label("Layout:");
+                        ui.checkbox(&mut self.config.compact_mode, "Compact mode")
+                            .on_hover_ui(|ui| {
+                                ui.label("Only shows a drop zone, the target size and the start button");
+                            }).changed().then(|| {
+                                self.config_dirty = true;
+                            });
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Sizes:");
+                        ui.checkbox(&mut self.config.exact_sizes, "Show exact bytes")
+                            .on_hover_ui(|ui| {
+                                ui.label("Shows sizes in the queue and summary as byte counts, for checking an output is under a hard limit");
+                            }).changed().then(|| {
+                                self.config_dirty = true;
+                            });
+                    });
+
                     ui.horizontal(|ui| {
                         ui.label("Theme:");
                         ui.checkbox(&mut self.config.dark_mode_enabled, "Dark Mode").changed().then(|| {
@@ -494,10 +3351,42 @@ impl eframe::App for MyApp {
                         });
                     });
 
-                    if self.config_dirty {
-                        confy::store(PROGRAM_CONFIG_NAME, None, &self.config).ok();
-                        self.config_dirty = false;
-                    }
+                    ui.horizontal(|ui| {
+                        ui.label("Queue finished:");
+                        ui.checkbox(&mut self.config.completion_sound, "Play a sound")
+                            .on_hover_ui(|ui| {
+                                ui.label("Plays a short chime when the last file in the queue is done, so you can step away while it works");
+                            }).changed().then(|| {
+                                self.config_dirty = true;
+                            });
+                        if ui.small_button("Test").clicked() {
+                            utils::play_completion_sound();
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Launching again:");
+                        ui.checkbox(&mut self.config.single_instance, "Add files to this window")
+                            .on_hover_ui(|ui| {
+                                ui.label("Opening videos with the program while it's running queues them here instead of opening a second window. Applies after a restart");
+                            }).changed().then(|| {
+                                self.config_dirty = true;
+                            });
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Updates:");
+                        ui.checkbox(&mut self.config.check_for_updates, "Check for updates on startup")
+                            .on_hover_ui(|ui| {
+                                ui.label("Asks GitHub for the latest release each time the program starts");
+                            }).changed().then(|| {
+                                self.config_dirty = true;
+                            });
+                    });
+                }
+
+                Tab::Summary => {
+                    self.summary_ui(ui);
                 }
 
                 Tab::Output => {
@@ -513,7 +3402,175 @@ impl eframe::App for MyApp {
                 }
             }
         });
+        self.confirmation_ui(ctx);
+        self.contact_sheet_ui(ctx);
+        self.item_log_ui(ctx);
+        self.shutdown_countdown_ui(ctx);
 
+        self.store_config_if_dirty();
         ctx.request_repaint();
     }
-}
\ No newline at end of file
+
+    // save the unfinished queue, kill a running encode so ffmpeg doesn't outlive the window, then
+    // give the worker a moment to delete its partial output
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.save_queue();
+        if !self.ffmpeg_busy.load(Ordering::SeqCst) {
+            return;
+        }
+        self.stop_after_current.store(true, Ordering::SeqCst);
+        self.cancel_current_job();
+
+        let deadline = Instant::now() + EXIT_CLEANUP_TIMEOUT;
+        while self.ffmpeg_busy.load(Ordering::SeqCst) && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+}
+
+// fixed width slot in a queue row
+fn queue_cell<R>(ui: &mut egui::Ui, width: f32, add_contents: impl FnOnce(&mut egui::Ui) -> R) -> R {
+    ui.allocate_ui_with_layout(
+        egui::vec2(width, ui.spacing().interact_size.y),
+        egui::Layout::left_to_right(egui::Align::Center),
+        |ui| {
+            ui.set_min_width(width);
+            ui.set_max_width(width);
+            add_contents(ui)
+        },
+    ).inner
+}
+
+// duration bar with draggable in/out handles over a strip of thumbnails. returns the new trim
+// when a handle moved, a trim covering the whole file is returned as None
+fn trim_timeline(ui: &mut egui::Ui, id: egui::Id, duration: f64, trim: Option<Trim>, thumbnails: &[egui::TextureHandle]) -> Option<Option<Trim>> {
+    let height = 40.0;
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(ui.available_width().min(480.0), height), egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+
+    let slot_width = rect.width() / thumbnails.len().max(1) as f32;
+    for (index, texture) in thumbnails.iter().enumerate() {
+        let slot = egui::Rect::from_min_size(rect.min + egui::vec2(index as f32 * slot_width, 0.0), egui::vec2(slot_width, height));
+        painter.image(texture.id(), slot, egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)), egui::Color32::WHITE);
+    }
+
+    let Trim { start, end } = trim.unwrap_or(Trim { start: 0.0, end: duration });
+    let x_for = |seconds: f64| rect.left() + (seconds / duration) as f32 * rect.width();
+    let seconds_for = |x: f32| (((x - rect.left()) / rect.width()).clamp(0.0, 1.0) as f64) * duration;
+
+    // dim what gets cut off
+    let dim = egui::Color32::from_black_alpha(160);
+    painter.rect_filled(egui::Rect::from_min_max(rect.min, egui::pos2(x_for(start), rect.bottom())), 0.0, dim);
+    painter.rect_filled(egui::Rect::from_min_max(egui::pos2(x_for(end), rect.top()), rect.max), 0.0, dim);
+
+    let mut changed = None;
+    for (handle, seconds) in [("in", start), ("out", end)] {
+        let handle_rect = egui::Rect::from_center_size(egui::pos2(x_for(seconds), rect.center().y), egui::vec2(10.0, height));
+        let response = ui.interact(handle_rect, id.with(handle), egui::Sense::drag())
+            .on_hover_cursor(egui::CursorIcon::ResizeHorizontal);
+        let color = if response.hovered() || response.dragged() {
+            ui.visuals().selection.stroke.color
+        } else {
+            ui.visuals().widgets.inactive.fg_stroke.color
+        };
+        painter.rect_filled(egui::Rect::from_center_size(handle_rect.center(), egui::vec2(4.0, height)), 1.0, color);
+
+        if response.dragged()
+            && let Some(pointer) = response.interact_pointer_pos()
+        {
+            // keep at least a second between the handles
+            let seconds = seconds_for(pointer.x);
+            changed = Some(match handle {
+                "in" => Trim { start: seconds.min(end - 1.0).max(0.0), end },
+                _ => Trim { start, end: seconds.max(start + 1.0).min(duration) },
+            });
+        }
+    }
+
+    changed.map(|trim| (trim.start > 0.0 || trim.end < duration).then_some(trim))
+}
+
+// lists are recognized by extension, or for extensionless files by being short plain text
+fn is_path_list(path: &Path) -> bool {
+    const MAX_LIST_BYTES: u64 = 1024 * 1024;
+    match path.extension().map(|e| e.to_string_lossy().to_ascii_lowercase()) {
+        Some(extension) => matches!(extension.as_str(), "txt" | "m3u" | "m3u8"),
+        None => std::fs::metadata(path).is_ok_and(|m| m.is_file() && m.len() <= MAX_LIST_BYTES)
+            && std::fs::read_to_string(path).is_ok(),
+    }
+}
+
+fn quality_color(ui: &egui::Ui, estimate: QualityEstimate) -> egui::Color32 {
+    match estimate {
+        QualityEstimate::Poor => ui.visuals().error_fg_color,
+        QualityEstimate::Fair => ui.visuals().warn_fg_color,
+        QualityEstimate::Good | QualityEstimate::Excellent => ui.visuals().text_color(),
+    }
+}
+
+fn status_name(status: &FileStatus) -> &'static str {
+    match status {
+        FileStatus::Waiting => "Waiting",
+        FileStatus::Processing => "Processing",
+        FileStatus::Done => "Done",
+        FileStatus::AlreadyOptimal => "Already optimal",
+        FileStatus::Failed(_) => "Failed",
+    }
+}
+
+// screen readers announce a widget by its own text, so checkboxes without one and emoji
+// labels get a spoken name here
+fn named_checkbox(ui: &mut egui::Ui, checked: &mut bool, name: &str) -> egui::Response {
+    let response = ui.checkbox(checked, "");
+    let selected = *checked;
+    response.widget_info(|| egui::WidgetInfo::selected(egui::WidgetType::Checkbox, response.enabled(), selected, name));
+    response
+}
+
+trait AccessibleName {
+    fn with_accessible_name(self, name: impl Into<String>) -> Self;
+}
+
+impl AccessibleName for egui::Response {
+    fn with_accessible_name(self, name: impl Into<String>) -> Self {
+        let name = name.into();
+        self.widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::Label, self.enabled(), &name));
+        self
+    }
+}
+
+// (display order, section header) of the group a status belongs to
+fn status_group(status: &FileStatus) -> (u8, &'static str) {
+    match status {
+        FileStatus::Processing => (0, "Processing"),
+        FileStatus::Waiting => (1, "Up Next"),
+        FileStatus::Done | FileStatus::AlreadyOptimal => (2, "Completed"),
+        FileStatus::Failed(_) => (3, "Failed"),
+    }
+}
+
+fn encoder_name(encoder: &Encoder) -> &'static str {
+    match encoder {
+        Encoder::CpuX264 => "libx264",
+        Encoder::GpuNvenc => "h264_nvenc",
+    }
+}
+
+fn summary_csv(items: &[QueueItem]) -> String {
+    let mut csv = String::from("file,input_bytes,output_bytes,ratio,seconds,encoder\n");
+    for item in items {
+        let name = item.path.file_name().unwrap_or_default().to_string_lossy().replace('"', "\"\"");
+        let ratio = item.output_size_bytes.map(|size| size as f64 / item.size_bytes.max(1) as f64);
+        csv.push_str(&format!(
+            "\"{}\",{},{},{},{},{}\n",
+            name,
+            item.size_bytes,
+            item.output_size_bytes.map_or(String::new(), |s| s.to_string()),
+            ratio.map_or(String::new(), |r| format!("{:.3}", r)),
+            item.elapsed.map_or(String::new(), |e| format!("{:.1}", e.as_secs_f64())),
+            item.encoder_used.as_ref().map_or("", encoder_name),
+        ));
+    }
+    csv
+}
@@ -0,0 +1,281 @@
+use std::{
+    path::PathBuf,
+    process::ExitCode,
+    sync::{mpsc, Mutex, atomic::AtomicBool},
+    thread,
+};
+
+use crate::{app, encode, utils};
+use crate::types::compression::{BitDepth, BitrateMode, ContentType, Encoder, H264_LEVELS, Preset, Profile, Resolution, SizeUnit, SourcePart};
+use crate::types::app::AppConfig;
+
+const USAGE: &str = "\
+Usage: video-compressor-gui [OPTIONS] <FILES>...
+
+Compresses each file to <name>_compressed.mp4 without opening the window.
+Options default to the settings saved by the GUI. Files given without any
+options open the window with them queued instead.
+
+Options:
+  --cli                   Run headless with the saved settings
+  --size <MB>             Target size in MB
+  --mib                   Treat sizes as MiB (1,048,576 bytes) instead of MB
+  --vmaf <SCORE>          Find the smallest size keeping this VMAF, instead of --size
+  --fps <N>               Output frame rate, e.g. 30 or 29.97
+  --interpolate           Make up new frames when --fps is above the source's, slow
+  --encoder <cpu|gpu>     Use libx264 or h264_nvenc
+  --resolution <1080|720|480|N%>
+  --upscale               Enlarge sources smaller than --resolution
+  --pad                   Output exactly 16:9 at --resolution, adding black bars to fit
+  --preset <NAME>         x264 preset, e.g. veryfast or slow
+  --content <TYPE>        Tune for general, film, animation, screen or grainy content
+  --10bit                 10-bit output, CPU encoder only
+  --profile <NAME>        H.264 profile: baseline, main or high
+  --level <N>             H.264 level, e.g. 4.0
+  --bitrate <KBPS>        Video bitrate to use instead of --size
+  --audio-bitrate <KBPS>  Audio bitrate to go with --bitrate
+  --cbr                   Constant bitrate instead of average bitrate
+  --two-pass              Analyze the video first for better quality at the same size
+  --quality <0-51>        Constant quality instead of a target size, lower is better
+  --audio-quality <Q>     AAC variable bitrate quality from 0.1 to 2 instead of a fixed bitrate
+  --copy-audio            Keep the source audio as is instead of re-encoding it
+  --split <MB>            Split the output into parts of this size
+  --match-container       Keep mkv/mov/m4v sources in their container
+  --subfolder             Write outputs to a compressed/ folder next to each file
+  --faststart             Optimize mp4 output for web playback
+  --keep-chapters         Copy chapter markers from the source
+  --auto-fps              Halve high frame rates when the bitrate is too low
+  --low-priority          Run ffmpeg at background priority
+  --cores <N>             Keep ffmpeg on the first N CPU cores, Linux and Windows only
+  --stall-timeout <SECS>  Stop an encode with no progress for this long, 0 to never
+  --discard-larger        Delete outputs that aren't smaller than the source
+  -h, --help              Print this help
+
+Anything after -- is a file, even if it starts with a dash.";
+
+// every option parse_args knows, only these pick headless mode over the window
+const OPTIONS: &[&str] = &[
+    "-h", "--help", "--cli", "--size", "--mib", "--vmaf", "--fps", "--interpolate", "--encoder", "--resolution",
+    "--upscale", "--pad", "--preset", "--content", "--10bit", "--profile", "--level", "--bitrate", "--audio-bitrate",
+    "--cbr", "--two-pass", "--quality", "--audio-quality", "--copy-audio", "--split", "--match-container",
+    "--subfolder", "--faststart", "--keep-chapters", "--auto-fps", "--low-priority", "--cores", "--stall-timeout",
+    "--discard-larger",
+];
+
+// a file named like -intro.mp4 from "open with" isn't an option, so the window still opens for it
+pub fn requested(args: &[String]) -> bool {
+    args.iter().take_while(|arg| *arg != "--").any(|arg| OPTIONS.contains(&arg.as_str()))
+}
+
+// headless entry point, compresses the given files one after another
+pub fn run(args: Vec<String>) -> ExitCode {
+    let (config, files) = match parse_args(args) {
+        Ok(Some(parsed)) => parsed,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut failures = 0;
+    for input in files {
+        let name = input.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let Ok(metadata) = std::fs::metadata(&input) else {
+            eprintln!("{}: file not found", name);
+            failures += 1;
+            continue;
+        };
+
+        println!("{}: compressing", name);
+        let (log_tx, log_rx) = mpsc::channel();
+        let job_config = config.clone();
+        let worker = thread::spawn(move || {
+            let current_child = Mutex::new(None);
+            let cancel_requested = AtomicBool::new(false);
+            encode::compress(&input, metadata.len(), SourcePart::default(), &job_config, &log_tx, &current_child, &cancel_requested);
+        });
+
+        // the channel closes once the worker returns
+        let mut succeeded = false;
+        let mut last_percent = None;
+        for line in log_rx {
+            if let Some(progress_str) = line.strip_prefix("[progress]:") {
+                if let Ok(progress) = progress_str.parse::<f32>() {
+                    let percent = (progress * 100.0) as u32;
+                    if last_percent != Some(percent) {
+                        println!("{}: {}%", name, percent);
+                        last_percent = Some(percent);
+                    }
+                }
+            } else if let Some(stage) = line.strip_prefix("[stage]:") {
+                // progress restarts for each stage
+                last_percent = None;
+                if !stage.is_empty() {
+                    println!("{}: {}", name, stage.to_lowercase());
+                }
+            } else if let Some(size_str) = line.strip_prefix("[output_size]:") {
+                let size = size_str.parse::<u64>().unwrap_or_default();
+                println!("{}: done, {}", name, utils::format_size(size));
+                succeeded = true;
+            } else if line == "[already_optimal]" {
+                println!("{}: already optimal, kept the original", name);
+                succeeded = true;
+            } else if let Some(reason) = line.strip_prefix("[failed]:") {
+                eprintln!("{}: {}", name, reason);
+                succeeded = false;
+            } else if !line.starts_with('[') {
+                eprintln!("{}", line);
+            }
+        }
+        worker.join().ok();
+
+        if !succeeded {
+            eprintln!("{}: failed", name);
+            failures += 1;
+        }
+    }
+
+    if failures > 0 { ExitCode::FAILURE } else { ExitCode::SUCCESS }
+}
+
+// returns None when help was requested
+fn parse_args(args: Vec<String>) -> Result<Option<(AppConfig, Vec<PathBuf>)>, String> {
+    let mut config: AppConfig = confy::load(app::PROGRAM_CONFIG_NAME, None).unwrap_or_default();
+    let mut files = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| args.next().ok_or(format!("{} needs a value", flag));
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--" => files.extend(args.by_ref().map(PathBuf::from)),
+            "--size" => {
+                config.target_size_mb = value(&arg)?.parse().map_err(|_| "--size must be a whole number of MB")?;
+            }
+            // only there to pick headless mode over the window
+            "--cli" => {}
+            "--mib" => config.size_unit = SizeUnit::Binary,
+            "--vmaf" => {
+                config.quality_target_vmaf = Some(value(&arg)?.parse().map_err(|_| "--vmaf must be a number")?);
+            }
+            "--fps" => {
+                config.frame_rate = Some(value(&arg)?.parse().ok().filter(|fps| *fps > 0.0).ok_or("--fps must be a positive number, e.g. 30 or 29.97")?);
+            }
+            "--interpolate" => config.interpolate_frames = true,
+            "--encoder" => {
+                config.encoder = match value(&arg)?.as_str() {
+                    "cpu" => Encoder::CpuX264,
+                    "gpu" => Encoder::GpuNvenc,
+                    other => return Err(format!("unknown encoder '{}'", other)),
+                };
+            }
+            "--resolution" => {
+                config.resolution = match value(&arg)?.trim_end_matches('p') {
+                    "1080" => Some(Resolution::R1080),
+                    "720" => Some(Resolution::R720),
+                    "480" => Some(Resolution::R480),
+                    percent if percent.ends_with('%') => match percent.trim_end_matches('%').parse() {
+                        Ok(percent @ 1..=100) => Some(Resolution::Percent(percent)),
+                        _ => return Err(format!("scale must be between 1% and 100%, got '{}'", percent)),
+                    },
+                    other => return Err(format!("unsupported resolution '{}'", other)),
+                };
+            }
+            "--upscale" => config.no_upscale = false,
+            "--pad" => config.pad_to_fit = true,
+            "--preset" => {
+                let name = value(&arg)?;
+                config.preset = Preset::from_name(&name).ok_or(format!("unknown preset '{}'", name))?;
+            }
+            "--content" => {
+                config.content_type = match value(&arg)?.as_str() {
+                    "general" => ContentType::General,
+                    "film" => ContentType::Film,
+                    "animation" => ContentType::Animation,
+                    "screen" => ContentType::ScreenCapture,
+                    "grainy" => ContentType::Grainy,
+                    other => return Err(format!("unknown content type '{}'", other)),
+                };
+            }
+            "--10bit" => config.bit_depth = BitDepth::Ten,
+            "--profile" => {
+                let name = value(&arg)?;
+                config.profile = Some(Profile::from_name(&name).ok_or(format!("unknown profile '{}'", name))?);
+            }
+            "--level" => {
+                let level = value(&arg)?;
+                if !H264_LEVELS.contains(&level.as_str()) {
+                    return Err(format!("unsupported level '{}', use one of {}", level, H264_LEVELS.join(", ")));
+                }
+                config.level = Some(level);
+            }
+            "--bitrate" => {
+                config.manual_video_bitrate_kbps = Some(value(&arg)?.parse().map_err(|_| "--bitrate must be a whole number of kbps")?);
+            }
+            "--audio-bitrate" => {
+                config.manual_audio_bitrate_kbps = Some(value(&arg)?.parse().map_err(|_| "--audio-bitrate must be a whole number of kbps")?);
+            }
+            "--cbr" => config.bitrate_mode = BitrateMode::Cbr,
+            "--two-pass" => config.bitrate_mode = BitrateMode::TwoPassAbr,
+            "--quality" => {
+                config.quality_level = value(&arg)?.parse().ok().filter(|level| *level <= 51).ok_or("--quality must be between 0 and 51")?;
+                config.bitrate_mode = BitrateMode::Crf;
+            }
+            "--audio-quality" => {
+                let quality: f32 = value(&arg)?.parse().map_err(|_| "--audio-quality must be a number")?;
+                if !(0.1..=2.0).contains(&quality) {
+                    return Err("--audio-quality must be between 0.1 and 2".to_string());
+                }
+                config.audio_quality = Some(quality);
+            }
+            "--copy-audio" => config.copy_audio = true,
+            "--split" => {
+                config.split_part_size_mb = Some(value(&arg)?.parse().map_err(|_| "--split must be a whole number of MB")?);
+            }
+            "--match-container" => config.match_source_container = true,
+            "--subfolder" => config.output_to_subfolder = true,
+            "--faststart" => config.faststart = true,
+            "--keep-chapters" => config.keep_chapters = true,
+            "--auto-fps" => config.auto_reduce_fps = true,
+            "--low-priority" => config.low_priority = true,
+            "--cores" => {
+                let count: u32 = value(&arg)?.parse().ok().filter(|count| *count > 0).ok_or("--cores must be a whole number above 0")?;
+                config.cpu_affinity = Some(utils::first_cores_mask(count.min(utils::cpu_core_count())));
+            }
+            "--stall-timeout" => {
+                let secs: u32 = value(&arg)?.parse().map_err(|_| "--stall-timeout must be a whole number of seconds")?;
+                config.stall_timeout_secs = (secs > 0).then_some(secs);
+            }
+            "--discard-larger" => config.discard_larger_output = true,
+            flag if flag.starts_with("--") => return Err(format!("unknown option '{}'", flag)),
+            _ => files.push(PathBuf::from(arg)),
+        }
+    }
+
+    if files.is_empty() {
+        return Err("no input files given".to_string());
+    }
+    Ok(Some((config, files)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn only_known_options_run_headless() {
+        assert!(requested(&args(&["--cli", "a.mp4"])));
+        assert!(requested(&args(&["a.mp4", "--size", "8"])));
+        assert!(!requested(&args(&["a.mp4", "b.mp4"])));
+        assert!(!requested(&args(&["-intro.mp4"])));
+        assert!(!requested(&args(&["--", "--size"])));
+    }
+}
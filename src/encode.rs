@@ -0,0 +1,2283 @@
+use std::{
+    io::{BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::{Mutex, OnceLock, atomic::{AtomicBool, Ordering}},
+    sync::mpsc::{self, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::utils;
+use crate::types::compression::{BitDepth, BitrateMode, Encoder, H264_LEVELS, ImageSequence, Profile, QualityEstimate, Resolution, SizeUnit, SourcePart, SubtitleSource, Trim};
+use crate::types::app::{AppConfig, MediaInfo, VideoStream};
+
+const OUTPUT_SUBFOLDER: &str = "compressed";
+// below this video bitrate, fewer frames at higher quality look better than more starved ones
+const AUTO_FPS_BITRATE_THRESHOLD: u32 = 500_000;
+// assumed for audio streams whose container doesn't report a bitrate
+const UNKNOWN_AUDIO_BITRATE: u32 = 128_000;
+
+// bitrates and frame rate compress settles on before building the command
+struct EncodePlan {
+    duration: f64,
+    source_duration: f64,
+    source_audio_bitrate: u32,
+    video_bitrate: u32,
+    audio_bitrate: u32,
+    // fps= or minterpolate=, None keeps the source's frame rate
+    frame_rate_filter: Option<String>,
+    // false when the bitrate isn't derived from the target size, e.g. constant quality or a VMAF search
+    size_targeted: bool,
+}
+
+// copied_audio_bitrate stands in for an audio bitrate the container doesn't report
+fn plan_encode(input: &Path, trim: Option<Trim>, config: &AppConfig, copied_audio_bitrate: Option<u32>, log_tx: &Sender<String>) -> Result<EncodePlan, String> {
+    let (source_duration, source_audio_bitrate) = get_duration_and_audio_bitrate(&ffmpeg_url(input))
+        .map_err(|err| format!("Failed to calculate bitrate: {}", err))?;
+    let source_audio_bitrate = source_audio_bitrate.or(copied_audio_bitrate).unwrap_or(UNKNOWN_AUDIO_BITRATE);
+    // the size budget only has to cover the kept part
+    let duration = match trim {
+        Some(trim) => (trim.end.min(source_duration) - trim.start).max(0.1),
+        None => source_duration,
+    };
+    let source_audio_bitrate = audio_budget(config, source_audio_bitrate);
+    let (video_bitrate, audio_bitrate) = calculate_bitrate(config, duration, source_audio_bitrate)
+        .ok_or("Failed to calculate bitrate.")?;
+
+    // an explicit frame rate always wins over the automatic reduction, which only applies to size targets
+    let mut fps = config.frame_rate.map(utils::frame_rate_arg);
+    let size_targeted_mode = config.quality_target_vmaf.is_none() && config.size_targeted();
+    if fps.is_none() && config.auto_reduce_fps && size_targeted_mode && video_bitrate < AUTO_FPS_BITRATE_THRESHOLD {
+        match get_frame_rate(&ffmpeg_url(input)) {
+            Some(source_fps) if source_fps >= 48.0 => {
+                let reduced_fps = source_fps / 2.0;
+                log_tx.send(format!(
+                    "Video bitrate of {} kbps is too low for {:.2} fps, reducing to {:.2} fps.",
+                    video_bitrate / 1000, source_fps, reduced_fps,
+                )).ok();
+                fps = Some(format!("{:.3}", reduced_fps));
+            }
+            Some(source_fps) => {
+                log_tx.send(format!("Video bitrate is low but {:.2} fps is already low, keeping it.", source_fps)).ok();
+            }
+            None => {
+                log_tx.send("Could not read source frame rate, keeping it.".to_string()).ok();
+            }
+        }
+    }
+
+    // raising the frame rate can make up the new frames from the motion around them instead of repeating
+    // frames. lowering it only drops frames, so the plain filter does that
+    let frame_rate_filter = match (fps, config.frame_rate) {
+        (Some(fps), Some(target)) if config.interpolate_frames => match get_frame_rate(&ffmpeg_url(input)) {
+            Some(source_fps) if target > source_fps + 0.01 => {
+                log_tx.send(format!("Interpolating from {:.2} to {} fps, this is much slower than a normal encode.", source_fps, fps)).ok();
+                Some(format!("minterpolate=fps={}:mi_mode=mci", fps))
+            }
+            Some(source_fps) => {
+                log_tx.send(format!("The source is already {:.2} fps, interpolation only applies when raising the frame rate.", source_fps)).ok();
+                Some(format!("fps={}", fps))
+            }
+            None => {
+                log_tx.send("Could not read source frame rate, not interpolating.".to_string()).ok();
+                Some(format!("fps={}", fps))
+            }
+        },
+        (fps, _) => fps.map(|fps| format!("fps={}", fps)),
+    };
+
+    Ok(EncodePlan {
+        duration,
+        source_duration,
+        source_audio_bitrate,
+        video_bitrate,
+        audio_bitrate,
+        frame_rate_filter,
+        // a constant quality encode has no bitrate to plan, the encoder decides the size
+        size_targeted: config.size_targeted(),
+    })
+}
+
+// the ffmpeg arguments for one planned encode, along with where it writes
+struct EncodeCommand {
+    args: Vec<String>,
+    output_path: PathBuf,
+    segment_time: Option<String>,
+    // analysis run before args for separate-pass encodes, and the stats file both runs share
+    first_pass: Option<(Vec<String>, PathBuf)>,
+}
+
+impl EncodeCommand {
+    fn command_line(&self) -> String {
+        match &self.first_pass {
+            Some((first_pass, _)) => format!("{} && {}", command_line(first_pass), command_line(&self.args)),
+            None => command_line(&self.args),
+        }
+    }
+}
+
+fn encode_command(input: &Path, part: SourcePart, config: &AppConfig, plan: &EncodePlan, log_tx: &Sender<String>) -> EncodeCommand {
+    let SourcePart { trim, video_stream } = part;
+    let source_info = probe_media_info(input).ok();
+    if !config.encoder.supports(config.bit_depth) {
+        log_tx.send("The GPU encoder can't write 10-bit H.264, encoding in 8-bit instead.".to_string()).ok();
+    }
+    if config.bit_depth == BitDepth::Ten && config.encoder.supports(BitDepth::Ten)
+        && let Some(profile) = config.profile
+    {
+        log_tx.send(format!("The {} profile can't hold 10-bit video, using high10 instead.", profile.as_str())).ok();
+    }
+    if let Some(level) = config.level.as_deref().filter(|level| !H264_LEVELS.contains(level)) {
+        log_tx.send(format!("Ignoring unknown H.264 level {}.", level)).ok();
+    }
+
+    // a part's length comes from the planned bitrate, which constant quality doesn't have
+    let split_part_size_mb = config.split_part_size_mb.filter(|_| config.bitrate_mode.size_targeted());
+    if config.split_part_size_mb.is_some() && split_part_size_mb.is_none() {
+        log_tx.send("Splitting needs a target size or bitrate to size the parts, writing a single file.".to_string()).ok();
+    }
+    let segment_time = split_part_size_mb
+        .map(|part_mb| format!("{:.3}", segment_duration(part_mb, config.size_unit, plan.video_bitrate + plan.audio_bitrate)));
+    let output_path = if segment_time.is_some() {
+        segment_output_pattern(input, config)
+    } else {
+        output_path_for(input, config)
+    };
+    if config.no_upscale
+        && let Some(resolution) = config.resolution.as_ref().filter(|r| !matches!(r, Resolution::Percent(_)))
+        && let Some(height) = source_info.as_ref().and_then(|i| i.height)
+        && height as f64 <= resolution.output_height(height as f64)
+    {
+        log_tx.send(format!("The source is {}p, not larger than {}, keeping its size.", height, resolution)).ok();
+    }
+    if let Some((width, height)) = padded_canvas(config) {
+        log_tx.send(format!("Fitting the picture inside {}x{} with black bars.", width, height)).ok();
+    }
+    let stream_map = stream_map_args(video_stream, source_info.as_ref());
+    let hardware_filters = hardware_scale_filters(input, &stream_map, config, plan.frame_rate_filter.as_deref(), log_tx);
+    let mut args = base_args(config);
+    if hardware_filters.is_some() {
+        args.extend(["-hwaccel", "cuda", "-hwaccel_output_format", "cuda"].map(String::from));
+    }
+    // seeking before -i is fast and makes -to count from the source's start
+    if let Some(trim) = trim {
+        args.extend(["-ss".to_string(), format!("{:.3}", trim.start), "-to".to_string(), format!("{:.3}", trim.end)]);
+    }
+    args.extend(["-i".to_string(), ffmpeg_url(input)]);
+
+    // insert optional parameters if specified
+    if trim.is_some() && config.burn_subtitles.is_some() {
+        log_tx.send("Subtitles are timed from the start of the source and may be out of sync on a trimmed file.".to_string()).ok();
+    }
+    let filters = hardware_filters.unwrap_or_else(|| video_filters(config, plan.frame_rate_filter.as_deref(), Some(input)));
+    if !filters.is_empty() {
+        args.extend(["-filter:v".to_string(), filters.join(",")]);
+    }
+    // the analysis pass is the same encode without audio, its output is thrown away
+    let pass_log = config.bitrate_mode.separate_passes(&config.encoder)
+        .then(|| scratch_path(config, &format!("{}_passlog", input.file_stem().unwrap_or_default().to_string_lossy())));
+    let mut first_pass = pass_log.as_ref().map(|_| args.clone());
+    if let Some(audio_filter) = audio_delay_filter(config) {
+        args.extend(["-filter:a".to_string(), audio_filter]);
+    }
+    let mut encoding_args = stream_map;
+    encoding_args.extend(encoder_args(config, plan.video_bitrate, plan.audio_bitrate));
+    if let (Some(first_pass), Some(pass_log)) = (&mut first_pass, &pass_log) {
+        first_pass.extend(encoding_args.iter().cloned());
+        first_pass.extend(["-pass".to_string(), "1".to_string(), "-passlogfile".to_string(), pass_log.to_string_lossy().into_owned()]);
+        first_pass.extend(["-an", "-f", "null", "-y", "-"].map(String::from));
+        encoding_args.extend(["-pass".to_string(), "2".to_string(), "-passlogfile".to_string(), pass_log.to_string_lossy().into_owned()]);
+    }
+    args.extend(encoding_args);
+    let color_stream = chosen_video_stream(video_stream, source_info.as_ref()).unwrap_or(0);
+    args.extend(color_args(config, &probe_color_tags(input, color_stream)));
+    args.extend(faststart_args(config, &output_path, segment_time.is_some()));
+    args.extend(metadata_args(config, input));
+
+    // cut into parts on forced keyframes so each part lands close to its size cap
+    if let Some(segment_time) = &segment_time {
+        args.extend([
+            "-force_key_frames", &format!("expr:gte(t,n_forced*{})", segment_time),
+            "-f", "segment",
+            "-segment_time", segment_time,
+            "-reset_timestamps", "1",
+        ].map(String::from));
+    }
+
+    // only map chapters when there are some, mapping a missing section errors out
+    if config.keep_chapters {
+        if has_chapters(&ffmpeg_url(input)) {
+            args.extend(["-map_chapters", "0"].map(String::from));
+        } else {
+            log_tx.send("No chapters found in source, skipping chapter copy.".to_string()).ok();
+        }
+    }
+
+    // output options must come before the output file. parts are many files, so they're written in place
+    if segment_time.is_some() {
+        args.extend(["-y".to_string(), ffmpeg_url(&output_path)]);
+    } else {
+        args.extend(output_target_args(config, &output_path));
+    }
+
+    EncodeCommand { args, output_path, segment_time, first_pass: first_pass.zip(pass_log) }
+}
+
+// the command compress would run for this file right now, for pasting into a terminal.
+// a VMAF target's bitrate is only known after its search, so the target size bitrate stands in for it
+pub fn preview_command(input: &Path, part: SourcePart, config: &AppConfig) -> Result<String, String> {
+    // the notes compress would log aren't wanted here
+    let (log_tx, _log_rx) = mpsc::channel();
+    let (fallback_config, copied_audio_bitrate) = without_uncopyable_audio(input, config, &log_tx);
+    let config = fallback_config.as_ref().unwrap_or(config);
+    let plan = plan_encode(input, part.trim, config, copied_audio_bitrate, &log_tx)?;
+    Ok(encode_command(input, part, config, &plan, &log_tx).command_line())
+}
+
+// ffmpeg picks the largest video stream on its own, which can be cover art. files with a single
+// video stream keep ffmpeg's automatic choice, otherwise the chosen or first real one and the
+// first audio stream are mapped
+fn stream_map_args(video_stream: Option<u32>, info: Option<&MediaInfo>) -> Vec<String> {
+    let Some(info) = info.filter(|i| i.video_streams.len() > 1 || video_stream.is_some()) else {
+        return Vec::new();
+    };
+    let Some(index) = chosen_video_stream(video_stream, Some(info)) else {
+        return Vec::new();
+    };
+    vec!["-map".to_string(), format!("0:v:{}", index), "-map".to_string(), "0:a:0?".to_string()]
+}
+
+// the picked stream, else the first one that isn't cover art
+fn chosen_video_stream(video_stream: Option<u32>, info: Option<&MediaInfo>) -> Option<u32> {
+    video_stream.or(info.and_then(|i| i.default_video_stream()))
+}
+
+fn command_line(args: &[String]) -> String {
+    format!("ffmpeg {}", args.iter()
+        .map(|s| utils::shell_quote(s))
+        .collect::<Vec<_>>()
+        .join(" ")
+    )
+}
+
+// compress one file, reporting log lines and [tagged] status messages through log_tx.
+// the running child is parked in current_child so another thread can kill it
+pub fn compress(
+    input: &Path,
+    source_size_bytes: u64,
+    part: SourcePart,
+    config: &AppConfig,
+    log_tx: &Sender<String>,
+    current_child: &Mutex<Option<Child>>,
+    cancel_requested: &AtomicBool,
+) {
+    let (fallback_config, copied_audio_bitrate) = without_uncopyable_audio(input, config, log_tx);
+    let config = fallback_config.as_ref().unwrap_or(config);
+    let mut plan = match plan_encode(input, part.trim, config, copied_audio_bitrate, log_tx) {
+        Ok(plan) => plan,
+        Err(err) => {
+            log_tx.send(err).ok();
+            return;
+        }
+    };
+
+    if config.quality_target_vmaf.is_some() && plan.size_targeted {
+        // never search above the source's own bitrate, a bigger file defeats the purpose
+        let max_bitrate = ((source_size_bytes as f64 * 8.0 / plan.source_duration) as u32).saturating_sub(plan.source_audio_bitrate);
+        match search_bitrate_for_quality(input, &plan, max_bitrate, config, log_tx, current_child, cancel_requested) {
+            Some(bitrate) => {
+                plan.size_targeted = false;
+                plan.video_bitrate = bitrate;
+                if !config.copy_audio {
+                    plan.audio_bitrate = plan.source_audio_bitrate.clamp(64_000, 256_000);
+                }
+            }
+            None => {
+                log_tx.send(format!("Falling back to the {} {} target size.", config.target_size_mb, config.size_unit.label())).ok();
+            }
+        }
+        log_tx.send("[stage]:".to_string()).ok();
+        if cancel_requested.load(Ordering::SeqCst) {
+            finish_outputs(&[], Ok(()), source_size_bytes, config, log_tx, cancel_requested);
+            return;
+        }
+    }
+
+    let command = encode_command(input, part, config, &plan, log_tx);
+    if !create_output_dir(&command.output_path, log_tx) {
+        return;
+    }
+    // kept on the item so the exact command can be copied later
+    log_tx.send(format!("[command]:{}", command.command_line())).ok();
+
+    log_bitrate_summary(config, plan.size_targeted, plan.video_bitrate, plan.audio_bitrate, plan.duration, log_tx);
+    // the planned size lets the caller learn how far the encoder tends to miss
+    if plan.size_targeted {
+        log_tx.send(format!("[predicted_size]:{}", planned_size(plan.video_bitrate + plan.audio_bitrate, plan.duration))).ok();
+    }
+    if let Some((first_pass, pass_log)) = &command.first_pass {
+        log_tx.send("[stage]:Analyzing (pass 1 of 2)".to_string()).ok();
+        remove_pass_logs(pass_log);
+        let result = run_ffmpeg(first_pass, plan.duration, config, log_tx, current_child, cancel_requested);
+        log_tx.send("[stage]:".to_string()).ok();
+        // the second pass sizes the encode from these stats, missing or stale ones give the wrong bitrate
+        let mut stats = pass_log.as_os_str().to_owned();
+        stats.push("-0.log");
+        let result = result.and_then(|()| match cancel_requested.load(Ordering::SeqCst) || Path::new(&stats).exists() {
+            true => Ok(()),
+            false => Err("ffmpeg wrote no stats".to_string()),
+        });
+        if result.is_err() || cancel_requested.load(Ordering::SeqCst) {
+            remove_pass_logs(pass_log);
+            let result = result.map_err(|reason| format!("first pass failed, {}", reason));
+            finish_outputs(&[], result, source_size_bytes, config, log_tx, cancel_requested);
+            return;
+        }
+    }
+    // parts are found by counting up from part000, so a longer earlier split would leave extra ones
+    // that get counted as this encode's
+    if command.segment_time.is_some() {
+        let stale_parts = segment_paths(input, config);
+        if !stale_parts.is_empty() {
+            log_tx.send(format!("Removing {} parts left from an earlier split of this file.", stale_parts.len())).ok();
+            for path in stale_parts {
+                std::fs::remove_file(path).ok();
+            }
+        }
+    }
+    let result = run_ffmpeg(&command.args, plan.duration, config, log_tx, current_child, cancel_requested);
+    if let Some((_, pass_log)) = &command.first_pass {
+        remove_pass_logs(pass_log);
+    }
+
+    let (output_files, result) = if command.segment_time.is_some() {
+        (segment_paths(input, config), result)
+    } else {
+        let (written, result) = publish_output(config, &command.output_path, result, cancel_requested);
+        (vec![written], result)
+    };
+    finish_outputs(&output_files, result, source_size_bytes, config, log_tx, cancel_requested);
+}
+
+// join several inputs into one output sized for their combined duration.
+// every input is normalized to the first one's frame size, frame rate and audio layout,
+// so clips with different codecs or resolutions can still be concatenated. video_streams holds each
+// input's chosen video stream, None takes its first one that isn't cover art
+pub fn compress_merged(
+    inputs: &[PathBuf],
+    video_streams: &[Option<u32>],
+    config: &AppConfig,
+    log_tx: &Sender<String>,
+    current_child: &Mutex<Option<Child>>,
+    cancel_requested: &AtomicBool,
+) {
+    let Some(first_input) = inputs.first() else {
+        return;
+    };
+    // the concat filter decodes every input's audio, so there's nothing left to copy
+    let config = &AppConfig { copy_audio: false, ..config.clone() };
+
+    let mut infos = Vec::new();
+    for input in inputs {
+        match probe_media_info(input) {
+            Ok(info) if info.duration.is_some() && info.width.is_some() => infos.push(info),
+            Ok(_) => {
+                log_tx.send(format!("Failed to merge: {} has no readable video duration", input.display())).ok();
+                return;
+            }
+            Err(err) => {
+                log_tx.send(format!("Failed to merge: {}: {}", input.display(), err)).ok();
+                return;
+            }
+        }
+    }
+
+    let duration: f64 = infos.iter().filter_map(|i| i.duration).sum();
+    let source_size_bytes = inputs.iter()
+        .filter_map(|p| std::fs::metadata(p).ok())
+        .map(|m| m.len())
+        .sum();
+    let with_audio = infos.iter().all(|i| i.audio_codec.is_some());
+    if !with_audio {
+        log_tx.send("Not every input has audio, the merged file will be silent.".to_string()).ok();
+    }
+    // a silent output leaves the whole budget to the video
+    let audio_bitrate = if with_audio {
+        get_duration_and_audio_bitrate(&ffmpeg_url(first_input))
+            .map(|(_, bitrate)| audio_budget(config, bitrate.unwrap_or(UNKNOWN_AUDIO_BITRATE)))
+            .unwrap_or(128_000)
+    } else {
+        0
+    };
+    let Some((video_bitrate, audio_bitrate)) = calculate_bitrate(config, duration, audio_bitrate) else {
+        log_tx.send("Failed to calculate bitrate.".to_string()).ok();
+        return;
+    };
+    if config.split_part_size_mb.is_some() {
+        log_tx.send("Splitting isn't supported when merging, writing a single file.".to_string()).ok();
+    }
+
+    // the concat filter needs identical streams, so normalize each input first
+    let streams: Vec<u32> = infos.iter().enumerate()
+        .map(|(index, info)| video_streams.get(index).copied().flatten().or(info.default_video_stream()).unwrap_or(0))
+        .collect();
+    let first_stream = infos[0].video_streams.iter().find(|s| s.index == streams[0]);
+    let width = first_stream.and_then(|s| s.width).or(infos[0].width).unwrap_or(1920);
+    let height = first_stream.and_then(|s| s.height).or(infos[0].height).unwrap_or(1080);
+    let frame_rate = infos[0].frame_rate.unwrap_or(30.0);
+
+    let mut filter_graph = String::new();
+    let mut concat_inputs = String::new();
+    for (index, stream) in streams.iter().enumerate() {
+        filter_graph.push_str(&format!(
+            "[{i}:v:{s}]scale={w}:{h}:force_original_aspect_ratio=decrease,pad={w}:{h}:(ow-iw)/2:(oh-ih)/2,setsar=1,fps={fps:.3},format=yuv420p[v{i}];",
+            i = index, s = stream, w = width, h = height, fps = frame_rate,
+        ));
+        concat_inputs.push_str(&format!("[v{}]", index));
+        if with_audio {
+            filter_graph.push_str(&format!("[{i}:a:0]aresample=48000,aformat=channel_layouts=stereo[a{i}];", i = index));
+            concat_inputs.push_str(&format!("[a{}]", index));
+        }
+    }
+    filter_graph.push_str(&format!("{}concat=n={}:v=1:a={}[vcat]", concat_inputs, inputs.len(), with_audio as u8));
+    if with_audio {
+        filter_graph.push_str("[acat]");
+    }
+
+    // the usual crop/fps/scale options apply to the joined video
+    if config.burn_subtitles.is_some() {
+        log_tx.send("Burning in subtitles isn't supported when merging, skipping them.".to_string()).ok();
+    }
+    let filters = video_filters(config, frame_rate_filter(config).as_deref(), None);
+    let video_label = if filters.is_empty() {
+        "[vcat]"
+    } else {
+        filter_graph.push_str(&format!(";[vcat]{}[vout]", filters.join(",")));
+        "[vout]"
+    };
+    let audio_label = match audio_delay_filter(config) {
+        Some(audio_filter) if with_audio => {
+            filter_graph.push_str(&format!(";[acat]{}[aout]", audio_filter));
+            "[aout]"
+        }
+        _ => "[acat]",
+    };
+
+    let output_path = output_file_name(first_input, config, &format!("_merged.{}", output_extension(first_input, config)));
+    if !create_output_dir(&output_path, log_tx) {
+        return;
+    }
+    let mut args = base_args(config);
+    for input in inputs {
+        args.extend(["-i".to_string(), ffmpeg_url(input)]);
+    }
+    args.extend(["-filter_complex", &filter_graph, "-map", video_label].map(String::from));
+    if with_audio {
+        args.extend(["-map", audio_label].map(String::from));
+    }
+    args.extend(encoder_args(config, video_bitrate, audio_bitrate));
+    args.extend(faststart_args(config, &output_path, false));
+    args.extend(metadata_args(config, first_input));
+    args.extend(output_target_args(config, &output_path));
+
+    log_tx.send(format!("Merging {} files into {}", inputs.len(), output_path.display())).ok();
+    log_bitrate_summary(config, config.size_targeted(), video_bitrate, audio_bitrate, duration, log_tx);
+    if config.size_targeted() {
+        log_tx.send(format!("[predicted_size]:{}", planned_size(video_bitrate + audio_bitrate, duration))).ok();
+    }
+    let result = run_ffmpeg(&args, duration, config, log_tx, current_child, cancel_requested);
+    let (written, result) = publish_output(config, &output_path, result, cancel_requested);
+    finish_outputs(&[written], result, source_size_bytes, config, log_tx, cancel_requested);
+}
+
+const SEQUENCE_EXTENSIONS: [&str; 7] = ["png", "jpg", "jpeg", "tif", "tiff", "bmp", "webp"];
+
+// a folder of numbered images, or one numbered image with siblings before or after it.
+// the numbering has to be contiguous, ffmpeg stops reading at the first gap
+pub fn detect_image_sequence(path: &Path, fps: u32) -> Option<ImageSequence> {
+    let is_image = |p: &Path| p.extension()
+        .is_some_and(|e| SEQUENCE_EXTENSIONS.contains(&e.to_string_lossy().to_ascii_lowercase().as_str()));
+    let frame = if path.is_dir() {
+        let mut images: Vec<PathBuf> = std::fs::read_dir(path).ok()?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| is_image(p))
+            .collect();
+        images.sort();
+        images.into_iter().next()?
+    } else if is_image(path) {
+        path.to_path_buf()
+    } else {
+        return None;
+    };
+
+    let stem = frame.file_stem()?.to_string_lossy().into_owned();
+    let extension = frame.extension()?.to_string_lossy().into_owned();
+    let prefix = stem.trim_end_matches(|c: char| c.is_ascii_digit());
+    let digits = &stem[prefix.len()..];
+    let number: u32 = digits.parse().ok()?;
+    // zero padded names keep their width, unpadded ones just count up
+    let width = if digits.len() > 1 && digits.starts_with('0') { digits.len() } else { 0 };
+    let frame_path = |n: u32| frame.with_file_name(format!("{}{:0width$}.{}", prefix, n, extension, width = width));
+
+    let mut start_number = number;
+    while start_number > 0 && frame_path(start_number - 1).exists() {
+        start_number -= 1;
+    }
+    let mut frame_count = 0;
+    let mut size_bytes = 0;
+    while let Ok(metadata) = std::fs::metadata(frame_path(start_number + frame_count)) {
+        frame_count += 1;
+        size_bytes += metadata.len();
+    }
+    if frame_count < 2 {
+        return None;
+    }
+
+    let number_pattern = if width > 0 { format!("%0{}d", width) } else { "%d".to_string() };
+    Some(ImageSequence {
+        pattern: frame.with_file_name(format!("{}{}.{}", prefix.replace('%', "%%"), number_pattern, extension)),
+        first_frame: frame_path(start_number),
+        start_number,
+        frame_count,
+        fps,
+        size_bytes,
+    })
+}
+
+// encode an image sequence to a silent video, sized for frame count / fps
+pub fn compress_image_sequence(
+    sequence: &ImageSequence,
+    source_size_bytes: u64,
+    config: &AppConfig,
+    log_tx: &Sender<String>,
+    current_child: &Mutex<Option<Child>>,
+    cancel_requested: &AtomicBool,
+) {
+    let duration = sequence.duration();
+    let Some((video_bitrate, _)) = calculate_bitrate(config, duration, 0) else {
+        log_tx.send("Failed to calculate bitrate.".to_string()).ok();
+        return;
+    };
+
+    let output_path = output_path_for(&sequence.name_path(), config);
+    if !create_output_dir(&output_path, log_tx) {
+        return;
+    }
+    let mut args = base_args(config);
+    args.extend([
+        "-framerate".to_string(), sequence.fps.to_string(),
+        "-start_number".to_string(), sequence.start_number.to_string(),
+        "-i".to_string(), ffmpeg_url(&sequence.pattern),
+    ]);
+    let mut filters = video_filters(config, frame_rate_filter(config).as_deref(), None);
+    // photos often have odd sizes and rgb pixels, which most players can't decode as H.264
+    filters.push("scale=trunc(iw/2)*2:trunc(ih/2)*2".to_string());
+    if !(config.bit_depth == BitDepth::Ten && config.encoder.supports(BitDepth::Ten)) {
+        filters.push("format=yuv420p".to_string());
+    }
+    args.extend(["-filter:v".to_string(), filters.join(",")]);
+    args.extend(encoder_args(config, video_bitrate, 0));
+    args.push("-an".to_string());
+    args.extend(faststart_args(config, &output_path, false));
+    args.extend(metadata_args(config, &sequence.name_path()));
+    args.extend(output_target_args(config, &output_path));
+
+    log_tx.send(format!(
+        "Encoding {} frames at {} fps from {}",
+        sequence.frame_count, sequence.fps, sequence.pattern.display(),
+    )).ok();
+    log_bitrate_summary(config, config.size_targeted(), video_bitrate, 0, duration, log_tx);
+    if config.size_targeted() {
+        log_tx.send(format!("[predicted_size]:{}", planned_size(video_bitrate, duration))).ok();
+    }
+    let result = run_ffmpeg(&args, duration, config, log_tx, current_child, cancel_requested);
+    let (written, result) = publish_output(config, &output_path, result, cancel_requested);
+    finish_outputs(&[written], result, source_size_bytes, config, log_tx, cancel_requested);
+}
+
+// re-encode a new recording to match an existing output, then join the two with a stream copy so
+// the existing part isn't encoded again. the new segment takes the existing file's frame size,
+// frame rate, bitrates, bit depth and audio layout, so both halves should come from the same encoder
+pub fn append_to_output(
+    input: &Path,
+    existing: &Path,
+    config: &AppConfig,
+    log_tx: &Sender<String>,
+    current_child: &Mutex<Option<Child>>,
+    cancel_requested: &AtomicBool,
+) {
+    let fail = |reason: String| {
+        log_tx.send(format!("Failed to append: {}", reason)).ok();
+        log_tx.send(format!("[failed]:{}", reason)).ok();
+    };
+    let (existing_info, input_info) = match (probe_media_info(existing), probe_media_info(input)) {
+        (Ok(existing_info), Ok(input_info)) => (existing_info, input_info),
+        (Err(err), _) | (_, Err(err)) => {
+            fail(err);
+            return;
+        }
+    };
+    let (Some(width), Some(height), Some(existing_duration), Some(input_duration)) =
+        (existing_info.width, existing_info.height, existing_info.duration, input_info.duration)
+    else {
+        fail("both files need a readable video stream and duration".to_string());
+        return;
+    };
+    let Some(params) = probe_stream_params(existing) else {
+        fail(format!("couldn't read the bitrates of {}", existing.display()));
+        return;
+    };
+    if params.audio.is_some() && input_info.audio_codec.is_none() {
+        fail("the existing output has audio but the new file doesn't".to_string());
+        return;
+    }
+    // the joined file keeps the existing output's codec headers, so the segment has to come out of the
+    // same encoder with the same codec, profile and pixel format
+    if params.codec != "h264" {
+        fail(format!("the existing output is {}, only H.264 outputs can be appended to", params.codec));
+        return;
+    }
+    if !matches!(params.pix_fmt.as_deref(), Some("yuv420p" | "yuv420p10le")) {
+        fail(format!("the existing output's pixel format ({}) can't be matched", params.pix_fmt.as_deref().unwrap_or("unknown")));
+        return;
+    }
+    let profile = match params.profile.as_deref() {
+        Some("Baseline" | "Constrained Baseline") => Some(Profile::Baseline),
+        Some("Main") => Some(Profile::Main),
+        Some("High") => Some(Profile::High),
+        // implied by the 10-bit output
+        Some("High 10") => None,
+        other => {
+            fail(format!("the existing output's H.264 profile ({}) can't be matched", other.unwrap_or("unknown")));
+            return;
+        }
+    };
+    let encoder = if made_by_x264(existing) {
+        Encoder::CpuX264
+    } else if config.encoder == Encoder::GpuNvenc {
+        Encoder::GpuNvenc
+    } else {
+        fail("the existing output wasn't encoded with x264, select the GPU encoder if it came from there".to_string());
+        return;
+    };
+    if encoder != config.encoder {
+        log_tx.send("Encoding the new segment on the CPU to match the existing output.".to_string()).ok();
+    }
+
+    // match the existing file instead of the usual target size and audio options
+    let mut segment_config = config.clone();
+    segment_config.bitrate_mode = BitrateMode::Abr;
+    segment_config.audio_quality = None;
+    segment_config.copy_audio = false;
+    segment_config.bit_depth = if params.ten_bit { BitDepth::Ten } else { BitDepth::Eight };
+    segment_config.encoder = encoder;
+    segment_config.profile = profile;
+    segment_config.level = params.level;
+    let audio_bitrate = match params.audio {
+        Some((sample_rate, channels, bitrate)) => {
+            segment_config.audio_sample_rate = Some(sample_rate);
+            segment_config.audio_channels = Some(channels);
+            bitrate
+        }
+        None => 0,
+    };
+
+    let extension = existing.extension().unwrap_or_default().to_string_lossy().into_owned();
+    let segment_path = scratch_path(config, &format!("append.{}", extension));
+    let list_path = scratch_path(config, "concat.txt");
+    let joined_path = existing.with_extension(format!("joined.{}", extension));
+    let cleanup = || {
+        for path in [&segment_path, &list_path, &joined_path] {
+            std::fs::remove_file(path).ok();
+        }
+    };
+
+    let mut args = base_args(config);
+    args.extend(["-i".to_string(), ffmpeg_url(input)]);
+    args.extend(["-filter:v".to_string(), format!(
+        "scale={w}:{h}:force_original_aspect_ratio=decrease,pad={w}:{h}:(ow-iw)/2:(oh-ih)/2,setsar=1,fps={fps:.3}",
+        w = width, h = height, fps = existing_info.frame_rate.unwrap_or(30.0),
+    )]);
+    args.extend(encoder_args(&segment_config, params.video_bitrate, audio_bitrate));
+    if params.audio.is_none() {
+        args.push("-an".to_string());
+    }
+    args.extend(["-y".to_string(), ffmpeg_url(&segment_path)]);
+
+    log_tx.send(format!("Appending {} to {}", input.display(), existing.display())).ok();
+    log_tx.send("[stage]:Encoding new segment".to_string()).ok();
+    if let Err(reason) = run_ffmpeg(&args, input_duration, config, log_tx, current_child, cancel_requested) {
+        cleanup();
+        log_tx.send(format!("[failed]:{}", reason)).ok();
+        return;
+    }
+    if cancel_requested.load(Ordering::SeqCst) {
+        cleanup();
+        finish_outputs(&[], Ok(()), 0, config, log_tx, cancel_requested);
+        return;
+    }
+
+    // the list sits in the temp folder, so entries are absolute. the concat demuxer reads
+    // single quoted names, a quote inside one is closed, escaped and reopened
+    let list_entry = |path: &Path| format!(
+        "file '{}'\n",
+        std::path::absolute(path).unwrap_or(path.to_path_buf()).to_string_lossy().replace('\'', "'\\''"),
+    );
+    if let Err(err) = std::fs::write(&list_path, list_entry(existing) + &list_entry(&segment_path)) {
+        cleanup();
+        fail(format!("couldn't write {} ({})", list_path.display(), err));
+        return;
+    }
+    let mut args = base_args(config);
+    args.extend(["-f", "concat", "-safe", "0", "-i"].map(String::from));
+    args.push(ffmpeg_url(&list_path));
+    args.extend(["-map", "0", "-c", "copy"].map(String::from));
+    args.extend(faststart_args(config, &joined_path, false));
+    args.extend(["-y".to_string(), ffmpeg_url(&joined_path)]);
+
+    log_tx.send("[stage]:Joining".to_string()).ok();
+    let result = run_ffmpeg(&args, existing_duration + input_duration, config, log_tx, current_child, cancel_requested);
+    log_tx.send("[stage]:".to_string()).ok();
+    if let Err(reason) = result {
+        cleanup();
+        log_tx.send(format!("[failed]:{}", reason)).ok();
+        return;
+    }
+    if cancel_requested.load(Ordering::SeqCst) {
+        cleanup();
+        finish_outputs(&[], Ok(()), 0, config, log_tx, cancel_requested);
+        return;
+    }
+
+    // only replace the existing output once the joined file holds all of it plus the new segment,
+    // the rename can't be undone
+    let existing_size = std::fs::metadata(existing).map_or(0, |m| m.len());
+    let joined_size = std::fs::metadata(&joined_path).map_or(0, |m| m.len());
+    let joined_duration = probe_media_info(&joined_path).ok().and_then(|info| info.duration);
+    let expected_duration = existing_duration + input_duration;
+    let complete = joined_size >= existing_size
+        && joined_duration.is_some_and(|d| (d - expected_duration).abs() <= APPEND_DURATION_TOLERANCE.max(expected_duration * 0.01));
+    if !complete {
+        cleanup();
+        fail(format!(
+            "the joined file came out incomplete ({}, {:.1} s of {:.1} s), the existing output is unchanged",
+            utils::format_size(joined_size), joined_duration.unwrap_or(0.0), expected_duration,
+        ));
+        return;
+    }
+    let replaced = std::fs::rename(&joined_path, existing);
+    cleanup();
+    match (replaced, std::fs::metadata(existing)) {
+        (Ok(()), Ok(metadata)) => {
+            log_tx.send(format!("Appended to {}", existing.display())).ok();
+            log_tx.send(format!("[output_size]:{}", metadata.len())).ok();
+        }
+        (Err(err), _) => fail(format!("couldn't replace the existing output ({})", err)),
+        (_, Err(err)) => fail(format!("couldn't read the joined output ({})", err)),
+    }
+}
+
+// seconds the joined file may differ from the two parts together, containers round the end of each part
+const APPEND_DURATION_TOLERANCE: f64 = 1.0;
+
+// what a segment has to match to be stream copied onto an existing file
+struct StreamParams {
+    codec: String,
+    // as ffprobe names it, e.g. "High" or "Constrained Baseline"
+    profile: Option<String>,
+    pix_fmt: Option<String>,
+    // e.g. "4.0", None when it isn't one of H264_LEVELS
+    level: Option<String>,
+    video_bitrate: u32,
+    ten_bit: bool,
+    // (sample rate, channels, bitrate)
+    audio: Option<(u32, u32, u32)>,
+}
+
+fn probe_stream_params(path: &Path) -> Option<StreamParams> {
+    let output = Command::new("ffprobe")
+        .args([
+            "-v", "error",
+            "-show_entries", "format=bit_rate:stream=codec_type,codec_name,profile,level,bit_rate,pix_fmt,sample_rate,channels",
+            "-of", "json",
+        ])
+        .arg(path)
+        .output()
+        .ok()?;
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).ok()?;
+    let streams = json["streams"].as_array()?;
+    // ffprobe reports numbers as strings in json
+    let number = |value: &serde_json::Value| value.as_str().and_then(|v| v.parse::<u32>().ok());
+
+    let video = streams.iter().find(|s| s["codec_type"] == "video")?;
+    let audio = streams.iter().find(|s| s["codec_type"] == "audio").map(|audio| (
+        number(&audio["sample_rate"]).unwrap_or(48_000),
+        audio["channels"].as_u64().unwrap_or(2) as u32,
+        number(&audio["bit_rate"]).unwrap_or(128_000),
+    ));
+    // mkv doesn't store per stream bitrates, so fall back to the overall rate minus audio
+    let video_bitrate = number(&video["bit_rate"])
+        .or_else(|| number(&json["format"]["bit_rate"]).map(|total| total.saturating_sub(audio.map_or(0, |a| a.2))))?;
+
+    // reported as 40 for level 4.0
+    let level = video["level"].as_u64()
+        .map(|level| format!("{}.{}", level / 10, level % 10))
+        .filter(|level| H264_LEVELS.contains(&level.as_str()));
+
+    Some(StreamParams {
+        codec: video["codec_name"].as_str().unwrap_or_default().to_string(),
+        profile: video["profile"].as_str().map(str::to_string),
+        pix_fmt: video["pix_fmt"].as_str().map(str::to_string),
+        level,
+        video_bitrate,
+        ten_bit: video["pix_fmt"].as_str().is_some_and(|f| f.contains("10")),
+        audio,
+    })
+}
+
+// x264 writes its version and settings as plain text into the first frame, which sits within the
+// first few MB even when the index was moved to the front
+const X264_TAG: &[u8] = b"x264 - core";
+const X264_TAG_SEARCH_BYTES: u64 = 8 * 1024 * 1024;
+
+fn made_by_x264(path: &Path) -> bool {
+    let mut head = Vec::new();
+    std::fs::File::open(path)
+        .and_then(|file| file.take(X264_TAG_SEARCH_BYTES).read_to_end(&mut head))
+        .is_ok_and(|_| head.windows(X264_TAG.len()).any(|window| window == X264_TAG))
+}
+
+const QUALITY_SEARCH_STEPS: u32 = 6;
+const QUALITY_SAMPLE_SECONDS: f64 = 10.0;
+const QUALITY_MIN_BITRATE: u32 = 50_000;
+
+// binary search for the lowest video bitrate whose sample encode still scores at least the
+// configured VMAF against the source. each step encodes and scores a short sample from the middle
+fn search_bitrate_for_quality(
+    input: &Path,
+    plan: &EncodePlan,
+    max_bitrate: u32,
+    config: &AppConfig,
+    log_tx: &Sender<String>,
+    current_child: &Mutex<Option<Child>>,
+    cancel_requested: &AtomicBool,
+) -> Option<u32> {
+    let threshold = config.quality_target_vmaf?;
+    let sample_length = plan.duration.min(QUALITY_SAMPLE_SECONDS);
+    let sample_start = ((plan.duration - sample_length) / 2.0).max(0.0);
+    let sample = Trim { start: sample_start, end: sample_start + sample_length };
+    let sample_path = scratch_path(config, "sample.mp4");
+
+    log_tx.send(format!(
+        "Searching for the smallest bitrate with a VMAF of at least {:.1}, this runs {} sample encodes.",
+        threshold, QUALITY_SEARCH_STEPS,
+    )).ok();
+
+    let (mut low, mut high) = (QUALITY_MIN_BITRATE, max_bitrate.max(QUALITY_MIN_BITRATE));
+    let mut best = high;
+    for step in 0..QUALITY_SEARCH_STEPS {
+        if cancel_requested.load(Ordering::SeqCst) {
+            break;
+        }
+        log_tx.send(format!("[stage]:Quality search {}/{}", step + 1, QUALITY_SEARCH_STEPS)).ok();
+        log_tx.send(format!("[progress]:{}", step as f32 / QUALITY_SEARCH_STEPS as f32)).ok();
+
+        let bitrate = low + (high - low) / 2;
+        let measured = measure_sample_vmaf(input, sample, bitrate, config, plan.frame_rate_filter.as_deref(), current_child, cancel_requested);
+        let Some(score) = measured else {
+            std::fs::remove_file(&sample_path).ok();
+            if !cancel_requested.load(Ordering::SeqCst) {
+                log_tx.send("Quality measurement failed, check that ffmpeg is built with libvmaf.".to_string()).ok();
+            }
+            return None;
+        };
+        log_tx.send(format!("Sample at {} kbps scored VMAF {:.2}", bitrate / 1000, score)).ok();
+        if score >= threshold as f64 {
+            best = bitrate;
+            high = bitrate;
+        } else {
+            low = bitrate;
+        }
+    }
+    std::fs::remove_file(&sample_path).ok();
+
+    log_tx.send(format!("Using a video bitrate of {} kbps.", best / 1000)).ok();
+    Some(best)
+}
+
+// the sample is written to the "sample.mp4" scratch file, which the caller removes
+fn measure_sample_vmaf(
+    input: &Path,
+    sample: Trim,
+    video_bitrate: u32,
+    config: &AppConfig,
+    frame_rate_filter: Option<&str>,
+    current_child: &Mutex<Option<Child>>,
+    cancel_requested: &AtomicBool,
+) -> Option<f64> {
+    let (start, length) = (format!("{:.3}", sample.start), format!("{:.3}", sample.end - sample.start));
+    let input = &ffmpeg_url(input);
+    let sample = &ffmpeg_url(&scratch_path(config, "sample.mp4"));
+
+    let mut encode_args: Vec<String> = [
+        "-nostdin", "-v", "error",
+        "-ss", &start, "-t", &length, "-i", input,
+    ].map(String::from).to_vec();
+    // black bars would be stretched over the picture when scaled back to the reference, and cost
+    // next to nothing, so the sample is scored without them
+    let unpadded = AppConfig { pad_to_fit: false, ..config.clone() };
+    let filters = video_filters(&unpadded, frame_rate_filter, None);
+    if !filters.is_empty() {
+        encode_args.extend(["-filter:v".to_string(), filters.join(",")]);
+    }
+    encode_args.extend(encoder_args(config, video_bitrate, 64_000));
+    encode_args.extend(["-an", "-y", sample].map(String::from));
+    let (encoded, _) = run_cancellable(&encode_args, current_child, cancel_requested)?;
+    if !encoded {
+        return None;
+    }
+
+    // compare against the source with the same crop and frame rate, scaling the sample back up to match
+    let mut reference_filters: Vec<String> = config.crop.iter().map(|c| c.to_filter()).collect();
+    // the reference only needs the same frame count, so an interpolated rate is matched with the plain filter
+    reference_filters.extend(frame_rate_filter.map(|f| match f.strip_prefix("minterpolate=") {
+        Some(options) => options.split(':').next().unwrap_or(options).to_string(),
+        None => f.to_string(),
+    }));
+    reference_filters.push("setpts=PTS-STARTPTS".to_string());
+    let graph = format!(
+        "[0:v]setpts=PTS-STARTPTS[dist];[1:v]{}[ref];[dist][ref]scale2ref=flags=bicubic[dist_scaled][ref_scaled];[dist_scaled][ref_scaled]libvmaf",
+        reference_filters.join(","),
+    );
+    let score_args = ["-nostdin", "-i", sample, "-ss", &start, "-t", &length, "-i", input, "-lavfi", &graph, "-f", "null", "-"].map(String::from);
+    let (_, log) = run_cancellable(&score_args, current_child, cancel_requested)?;
+
+    log.lines()
+        .find_map(|line| line.split_once("VMAF score:"))
+        .and_then(|(_, score)| score.trim().parse().ok())
+}
+
+pub const THUMBNAIL_WIDTH: usize = 96;
+pub const THUMBNAIL_HEIGHT: usize = 54;
+
+// evenly spaced frames as raw RGBA, letterboxed to THUMBNAIL_WIDTH x THUMBNAIL_HEIGHT
+pub fn extract_thumbnails(path: &Path, duration: f64, count: usize) -> Vec<Vec<u8>> {
+    let filter = format!(
+        "scale={w}:{h}:force_original_aspect_ratio=decrease,pad={w}:{h}:(ow-iw)/2:(oh-ih)/2",
+        w = THUMBNAIL_WIDTH, h = THUMBNAIL_HEIGHT,
+    );
+    (0..count)
+        .filter_map(|index| {
+            let timestamp = format!("{:.3}", duration * (index as f64 + 0.5) / count as f64);
+            let output = Command::new("ffmpeg")
+                .args(["-nostdin", "-v", "error", "-ss", &timestamp])
+                .arg("-i")
+                .arg(path)
+                .args(["-frames:v", "1", "-vf", &filter, "-f", "rawvideo", "-pix_fmt", "rgba", "-"])
+                .stdin(Stdio::null())
+                .output()
+                .ok()?;
+            (output.stdout.len() == THUMBNAIL_WIDTH * THUMBNAIL_HEIGHT * 4).then_some(output.stdout)
+        })
+        .collect()
+}
+
+const CONTACT_SHEET_COLUMNS: usize = 4;
+const CONTACT_SHEET_ROWS: usize = 4;
+const CONTACT_SHEET_TILE_WIDTH: usize = 240;
+const CONTACT_SHEET_TILE_HEIGHT: usize = 135;
+pub const CONTACT_SHEET_WIDTH: usize = CONTACT_SHEET_COLUMNS * CONTACT_SHEET_TILE_WIDTH;
+pub const CONTACT_SHEET_HEIGHT: usize = CONTACT_SHEET_ROWS * CONTACT_SHEET_TILE_HEIGHT;
+
+// where the sheet was saved and its pixels as RGBA
+pub type ContactSheet = (PathBuf, Vec<u8>);
+
+// frames at even intervals over the whole video tiled into one image, saved as a jpg next to the
+// video. the same image comes back as raw RGBA for display, so it's only decoded once
+pub fn create_contact_sheet(video: &Path) -> Result<ContactSheet, String> {
+    let duration = probe_media_info(video)?.duration.ok_or("the video has no readable duration")?;
+    let sheet_path = video.with_file_name(format!("{}_contact_sheet.jpg", video.file_stem().unwrap_or_default().to_string_lossy()));
+    let filter = format!(
+        "fps={n}/{d:.3},scale={w}:{h}:force_original_aspect_ratio=decrease,pad={w}:{h}:(ow-iw)/2:(oh-ih)/2,tile={c}x{r},split=2[file][raw]",
+        n = CONTACT_SHEET_COLUMNS * CONTACT_SHEET_ROWS, d = duration.max(0.1),
+        w = CONTACT_SHEET_TILE_WIDTH, h = CONTACT_SHEET_TILE_HEIGHT,
+        c = CONTACT_SHEET_COLUMNS, r = CONTACT_SHEET_ROWS,
+    );
+    let output = Command::new("ffmpeg")
+        .args(["-nostdin", "-v", "error", "-i", &ffmpeg_url(video), "-filter_complex", &filter])
+        .args(["-map", "[file]", "-frames:v", "1", "-update", "1", "-y", &ffmpeg_url(&sheet_path)])
+        .args(["-map", "[raw]", "-frames:v", "1", "-f", "rawvideo", "-pix_fmt", "rgba", "-"])
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("could not run ffmpeg ({})", e))?;
+    if output.stdout.len() != CONTACT_SHEET_WIDTH * CONTACT_SHEET_HEIGHT * 4 {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(if stderr.is_empty() { "ffmpeg produced no image".to_string() } else { stderr });
+    }
+    Ok((sheet_path, output.stdout))
+}
+
+const SELF_TEST_SECONDS: f64 = 5.0;
+
+// encodes a generated test pattern with the configured encoder, so encoder problems can be told apart
+// from problems with a particular input. returns the encode speed relative to real time
+pub fn run_self_test(config: &AppConfig) -> Result<f64, String> {
+    let source = format!("testsrc=duration={}:size=1280x720:rate=30", SELF_TEST_SECONDS);
+    let mut args: Vec<String> = ["-nostdin", "-v", "error", "-f", "lavfi", "-i", &source].map(String::from).to_vec();
+    args.extend(encoder_args(config, 2_000_000, 128_000));
+    args.extend(["-an", "-f", "null", "-"].map(String::from));
+
+    let started = std::time::Instant::now();
+    let output = Command::new("ffmpeg")
+        .args(&args)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("could not run ffmpeg ({})", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(if stderr.is_empty() {
+            format!("ffmpeg exited with {}", output.status)
+        } else {
+            stderr
+        });
+    }
+    Ok(SELF_TEST_SECONDS / started.elapsed().as_secs_f64().max(0.001))
+}
+
+// ffmpeg reads "name:" at the start of a path as a protocol, so a relative path like "clip 1:30.mp4"
+// needs the explicit file: protocol. absolute paths can't be mistaken for one
+fn ffmpeg_url(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    let before_separator = path.split(['/', '\\']).next().unwrap_or_default();
+    if before_separator.contains(':') && !Path::new(path.as_ref()).is_absolute() {
+        format!("file:{}", path)
+    } else {
+        path.into_owned()
+    }
+}
+
+// x264 writes its stats next to the -passlogfile prefix, plus .temp copies while a pass runs
+fn remove_pass_logs(prefix: &Path) {
+    for suffix in ["-0.log", "-0.log.mbtree", "-0.log.temp", "-0.log.mbtree.temp"] {
+        let mut path = prefix.as_os_str().to_owned();
+        path.push(suffix);
+        std::fs::remove_file(path).ok();
+    }
+}
+
+// scratch file in the configured temp folder, named per process so two instances don't collide
+fn scratch_path(config: &AppConfig, name: &str) -> PathBuf {
+    let dir = config.temp_dir.clone().unwrap_or_else(std::env::temp_dir);
+    std::fs::create_dir_all(&dir).ok();
+    dir.join(format!("video_compressor_gui_{}_{}", std::process::id(), name))
+}
+
+// progress goes to stdout as key=value pairs, stdin is never read
+fn base_args(config: &AppConfig) -> Vec<String> {
+    [
+        "-nostdin",
+        "-v", config.log_level.as_str(),
+        "-nostats",
+        "-progress", "pipe:1",
+    ].map(String::from).to_vec()
+}
+
+// the plain fps filter for the chosen frame rate, for joins and sequences that aren't interpolated
+fn frame_rate_filter(config: &AppConfig) -> Option<String> {
+    config.frame_rate.map(|fps| format!("fps={}", utils::frame_rate_arg(fps)))
+}
+
+// every value here is a number or a fixed keyword, except the subtitle file name, which goes
+// through utils::escape_filter_value so commas, colons and quotes in paths can't break the graph.
+// subtitles are only burned in when the input they belong to is given
+fn video_filters(config: &AppConfig, frame_rate_filter: Option<&str>, subtitles_input: Option<&Path>) -> Vec<String> {
+    let mut filters = Vec::new();
+    if let Some(crop) = &config.crop {
+        filters.push(crop.to_filter());
+    }
+    // rendered after cropping so the text stays inside the visible picture
+    if let (Some(source), Some(input)) = (&config.burn_subtitles, subtitles_input) {
+        filters.push(subtitles_filter(source, input));
+    }
+    if let Some(frame_rate_filter) = frame_rate_filter {
+        filters.push(frame_rate_filter.to_string());
+    }
+    if let Some(res) = &config.resolution {
+        let flags = config.scale_algorithm.as_str().map(|flags| format!(":flags={}", flags)).unwrap_or_default();
+        match padded_canvas(config) {
+            Some((width, height)) => {
+                // shrink to fit inside the frame keeping the aspect ratio, then center it on black
+                let size = if config.no_upscale {
+                    format!("'min(iw,{})':'min(ih,{})'", width, height)
+                } else {
+                    format!("{}:{}", width, height)
+                };
+                filters.push(format!("scale={}:force_original_aspect_ratio=decrease:force_divisible_by=2{}", size, flags));
+                filters.push(format!("pad={}:{}:(ow-iw)/2:(oh-ih)/2", width, height));
+                filters.push("setsar=1".to_string());
+            }
+            None => filters.push(format!("scale={}{}", res.to_scale_size(config.no_upscale), flags)),
+        }
+    }
+    filters
+}
+
+// the exact output size when fitting with padding, None when the aspect ratio decides the width
+fn padded_canvas(config: &AppConfig) -> Option<(u32, u32)> {
+    config.resolution.as_ref().filter(|_| config.pad_to_fit)?.canvas_size()
+}
+
+// styled .ass subtitles keep their own look, plain formats get a readable default
+const SUBTITLE_STYLE: &str = "FontName=Arial,FontSize=22,Outline=1,Shadow=0";
+
+// resizing for the gpu encoder on the gpu as well, so decoded frames don't travel to system memory
+// and back. only possible while every other filter can work on gpu frames, crop and subtitles can't
+fn hardware_scale_filters(input: &Path, stream_map: &[String], config: &AppConfig, frame_rate_filter: Option<&str>, log_tx: &Sender<String>) -> Option<Vec<String>> {
+    let resolution = config.resolution.as_ref()?;
+    if config.encoder != Encoder::GpuNvenc {
+        return None;
+    }
+    if frame_rate_filter.is_some_and(|filter| filter.starts_with("minterpolate")) {
+        log_tx.send("Frame interpolation needs the CPU, scaling on the CPU as well.".to_string()).ok();
+        return None;
+    }
+    if config.crop.is_some() || config.burn_subtitles.is_some() {
+        log_tx.send("Cropping and subtitles need the CPU, scaling on the CPU as well.".to_string()).ok();
+        return None;
+    }
+    if padded_canvas(config).is_some() {
+        log_tx.send("Padding needs the CPU, scaling on the CPU as well.".to_string()).ok();
+        return None;
+    }
+    if !ffmpeg_has_filter("scale_cuda") {
+        log_tx.send("This ffmpeg build has no scale_cuda filter, scaling on the CPU instead, which is slower.".to_string()).ok();
+        return None;
+    }
+
+    let mut filters = Vec::new();
+    if let Some(frame_rate_filter) = frame_rate_filter {
+        filters.push(frame_rate_filter.to_string());
+    }
+    let interpolation = match config.scale_algorithm.as_str() {
+        Some(algorithm @ ("bilinear" | "bicubic" | "lanczos")) => format!(":interp_algo={}", algorithm),
+        _ => String::new(),
+    };
+    // 10-bit sources decode to p010, which h264_nvenc can't take
+    filters.push(format!("scale_cuda={}:format=nv12{}", resolution.to_scale_size(config.no_upscale), interpolation));
+    if !gpu_decodes(input, stream_map, &filters) {
+        log_tx.send("The GPU can't decode this source, scaling on the CPU instead.".to_string()).ok();
+        return None;
+    }
+    log_tx.send("Decoding and scaling on the GPU.".to_string()).ok();
+    Some(filters)
+}
+
+// a codec or pixel format nvdec can't handle falls back to software decoding, whose frames the cuda
+// filters then reject. running the first frame through the same graph finds that out up front
+fn gpu_decodes(input: &Path, stream_map: &[String], filters: &[String]) -> bool {
+    Command::new("ffmpeg")
+        .args(["-nostdin", "-v", "error", "-hwaccel", "cuda", "-hwaccel_output_format", "cuda", "-i"])
+        .arg(ffmpeg_url(input))
+        .args(stream_map)
+        .args(["-an", "-frames:v", "1", "-filter:v", &filters.join(","), "-f", "null", "-"])
+        .stdin(Stdio::null())
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+// the filter list doesn't change while the program runs, so ffmpeg is only asked once
+fn ffmpeg_has_filter(name: &str) -> bool {
+    static FILTERS: OnceLock<String> = OnceLock::new();
+    FILTERS.get_or_init(|| Command::new("ffmpeg")
+        .args(["-hide_banner", "-filters"])
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+        .unwrap_or_default())
+        .lines()
+        .any(|line| line.split_whitespace().nth(1) == Some(name))
+}
+
+fn subtitles_filter(source: &SubtitleSource, input: &Path) -> String {
+    let (file, stream_index) = match source {
+        SubtitleSource::Embedded(index) => (input, Some(*index)),
+        SubtitleSource::External(path) => (path.as_path(), None),
+    };
+    let mut filter = format!("subtitles=filename={}", utils::escape_filter_value(&file.to_string_lossy()));
+    if let Some(index) = stream_index {
+        filter.push_str(&format!(":si={}", index));
+    }
+    let styled = file.extension().is_some_and(|e| e.eq_ignore_ascii_case("ass")) && stream_index.is_none();
+    if !styled {
+        filter.push_str(&format!(":force_style={}", utils::escape_filter_value(SUBTITLE_STYLE)));
+    }
+    filter
+}
+
+// shifts audio against video: a positive delay pads the start with silence, a negative one trims it
+fn audio_delay_filter(config: &AppConfig) -> Option<String> {
+    match config.audio_delay_ms {
+        0 => None,
+        delay if delay > 0 => Some(format!("adelay={}:all=1", delay)),
+        delay => Some(format!("atrim=start={:.3},asetpts=PTS-STARTPTS", -delay as f64 / 1000.0)),
+    }
+}
+
+fn encoder_args(config: &AppConfig, video_bitrate: u32, audio_bitrate: u32) -> Vec<String> {
+    let b_a = audio_bitrate.to_string();
+    let rate_control_args = config.bitrate_mode.encoder_args(&config.encoder, video_bitrate, config.quality_level);
+    let mut args = vec![
+        "-c:v",
+        match config.encoder {
+            Encoder::CpuX264 => "libx264",
+            Encoder::GpuNvenc => "h264_nvenc",
+        },
+    ];
+    args.extend(rate_control_args.iter().map(String::as_str));
+    let q_a = config.audio_quality.map(|q| format!("{:.2}", q.clamp(0.1, 2.0)));
+    match &q_a {
+        _ if config.copy_audio => args.extend(["-c:a", "copy"]),
+        Some(q_a) => args.extend(["-c:a", "aac", "-q:a", q_a]),
+        None => args.extend(["-c:a", "aac", "-b:a", &b_a]),
+    }
+
+    // resampling and downmixing need the audio decoded
+    let sample_rate = config.audio_sample_rate.filter(|_| !config.copy_audio).map(|r| r.to_string());
+    if let Some(sample_rate) = &sample_rate {
+        args.extend(["-ar", sample_rate]);
+    }
+    let channels = config.audio_channels.filter(|_| !config.copy_audio).map(|c| c.to_string());
+    if let Some(channels) = &channels {
+        args.extend(["-ac", channels]);
+    }
+
+    if let Some(preset_str) = config.preset.as_str() {
+        args.extend(["-preset", preset_str]);
+    }
+    args.extend(config.content_type.encoder_args(&config.encoder));
+
+    // 10-bit needs the high10 profile, so it takes precedence over a chosen 8-bit profile
+    if config.bit_depth == BitDepth::Ten && config.encoder.supports(BitDepth::Ten) {
+        args.extend(["-pix_fmt", "yuv420p10le", "-profile:v", "high10"]);
+    } else if let Some(profile) = config.profile {
+        args.extend(["-profile:v", profile.as_str()]);
+    }
+    if let Some(level) = config.level.as_deref().filter(|level| H264_LEVELS.contains(level)) {
+        args.extend(["-level", level]);
+    }
+
+    args.into_iter().map(String::from).collect()
+}
+
+// color tags ffprobe reports for the first video stream, None where unknown
+#[derive(Default)]
+struct ColorTags {
+    range: Option<String>,
+    space: Option<String>,
+    primaries: Option<String>,
+    transfer: Option<String>,
+}
+
+// tags of the stream that gets encoded, cover art has its own full range jpeg ones
+fn probe_color_tags(input: &Path, video_stream: u32) -> ColorTags {
+    let Ok(output) = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", &format!("v:{}", video_stream)])
+        .args([
+            "-show_entries", "stream=color_range,color_space,color_primaries,color_transfer",
+            "-of", "default=noprint_wrappers=1",
+        ])
+        .arg(ffmpeg_url(input))
+        .output()
+    else {
+        return ColorTags::default();
+    };
+
+    let mut tags = ColorTags::default();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    for (key, value) in stdout.lines().filter_map(|l| l.trim().split_once('=')) {
+        let value = (!matches!(value, "unknown" | "N/A" | "")).then(|| value.to_string());
+        match key {
+            "color_range" => tags.range = value,
+            "color_space" => tags.space = value,
+            "color_primaries" => tags.primaries = value,
+            "color_transfer" => tags.transfer = value,
+            _ => {}
+        }
+    }
+    tags
+}
+
+// the encoder writes untagged output by default, which players read as limited range bt.601 or
+// bt.709 depending on the resolution, so full range or bt.601 sources come out washed out or shifted
+fn color_args(config: &AppConfig, tags: &ColorTags) -> Vec<String> {
+    let mut args = Vec::new();
+    if config.preserve_color_tags {
+        let flags = [
+            ("-colorspace", &tags.space),
+            ("-color_primaries", &tags.primaries),
+            ("-color_trc", &tags.transfer),
+        ];
+        for (flag, value) in flags {
+            if let Some(value) = value {
+                args.extend([flag.to_string(), value.clone()]);
+            }
+        }
+    }
+    let range = match config.color_range {
+        Some(range) => Some(range.as_str().to_string()),
+        None if config.preserve_color_tags => tags.range.clone(),
+        None => None,
+    };
+    if let Some(range) = range {
+        args.extend(["-color_range".to_string(), range]);
+    }
+    args
+}
+
+// an empty title falls back to the source's file name
+fn metadata_args(config: &AppConfig, input: &Path) -> Vec<String> {
+    let mut args = Vec::new();
+    if let Some(title) = &config.metadata_title {
+        let title = match title.trim() {
+            "" => input.file_stem().unwrap_or_default().to_string_lossy().into_owned(),
+            title => title.to_string(),
+        };
+        args.extend(["-metadata".to_string(), format!("title={}", title)]);
+    }
+    if !config.metadata_comment.trim().is_empty() {
+        args.extend(["-metadata".to_string(), format!("comment={}", config.metadata_comment.trim())]);
+    }
+    args
+}
+
+// moving the moov atom to the front only applies to mp4-family containers
+fn faststart_args(config: &AppConfig, output_path: &Path, segmented: bool) -> Vec<String> {
+    let faststart_container = output_path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| matches!(e.to_ascii_lowercase().as_str(), "mp4" | "mov" | "m4v"));
+    if !config.faststart || !faststart_container {
+        return Vec::new();
+    }
+
+    if segmented {
+        ["-segment_format_options", "movflags=+faststart"].map(String::from).to_vec()
+    } else {
+        ["-movflags", "+faststart"].map(String::from).to_vec()
+    }
+}
+
+// lets other programs go first when they need the cpu, the encode only uses what's left over
+#[cfg(unix)]
+fn set_low_priority(command: &mut Command) {
+    use std::os::unix::process::CommandExt;
+
+    // safety: setpriority is async-signal-safe and only touches the forked child
+    unsafe {
+        command.pre_exec(|| {
+            libc::setpriority(libc::PRIO_PROCESS, 0, 10);
+            Ok(())
+        });
+    }
+}
+
+#[cfg(windows)]
+fn set_low_priority(command: &mut Command) {
+    use std::os::windows::process::CommandExt;
+
+    const IDLE_PRIORITY_CLASS: u32 = 0x0000_0040;
+    command.creation_flags(IDLE_PRIORITY_CLASS);
+}
+
+#[cfg(not(any(unix, windows)))]
+fn set_low_priority(_command: &mut Command) {}
+
+// keeps ffmpeg on the chosen cores so the others stay free. linux sets it before ffmpeg starts,
+// windows on the started process, macOS has no way to pin a process. whether it worked is checked by
+// child_cpu_affinity once ffmpeg is running
+#[cfg(target_os = "linux")]
+fn set_cpu_affinity(command: &mut Command, mask: u64) {
+    use std::os::unix::process::CommandExt;
+
+    // safety: sched_setaffinity is async-signal-safe and only touches the forked child
+    unsafe {
+        command.pre_exec(move || {
+            let mut set: libc::cpu_set_t = std::mem::zeroed();
+            for core in (0..64).filter(|core| mask & (1 << core) != 0) {
+                libc::CPU_SET(core, &mut set);
+            }
+            libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set);
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_cpu_affinity(_command: &mut Command, _mask: u64) {}
+
+// whether the running ffmpeg is limited to the mask, None where that isn't supported.
+// linux only reads back what set_cpu_affinity pinned before the start
+#[cfg(target_os = "linux")]
+fn child_cpu_affinity(child: &Child, mask: u64) -> Option<bool> {
+    // safety: the set is plain data that sched_getaffinity fills in
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        if libc::sched_getaffinity(child.id() as libc::pid_t, std::mem::size_of::<libc::cpu_set_t>(), &mut set) != 0 {
+            return Some(false);
+        }
+        Some((0..64).all(|core| libc::CPU_ISSET(core, &set) == (mask & (1 << core) != 0)))
+    }
+}
+
+// windows pins the process here, after it started
+#[cfg(windows)]
+fn child_cpu_affinity(child: &Child, mask: u64) -> Option<bool> {
+    use std::os::windows::io::AsRawHandle;
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn SetProcessAffinityMask(process: *mut std::ffi::c_void, mask: usize) -> i32;
+    }
+    // safety: the handle stays open for as long as the Child is alive
+    Some(unsafe { SetProcessAffinityMask(child.as_raw_handle(), mask as usize) != 0 })
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+fn child_cpu_affinity(_child: &Child, _mask: u64) -> Option<bool> {
+    None
+}
+
+const STALL_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+// reads ffmpeg's -progress output, key=value blocks each terminated by progress=continue or progress=end
+#[derive(Default)]
+struct ProgressParser {
+    out_time_us: u64,
+    speed: Option<f32>,
+}
+
+#[derive(Debug, PartialEq)]
+enum ProgressLine {
+    // the encoded time moved forward
+    Advanced,
+    // end of a block, with the fraction of the duration done and the speed if ffmpeg knows it
+    BlockEnd(f64, Option<f32>),
+    Other,
+}
+
+impl ProgressParser {
+    fn feed(&mut self, line: &str, duration: f64) -> ProgressLine {
+        let Some((key, value)) = line.split_once('=') else {
+            return ProgressLine::Other;
+        };
+        match key {
+            "out_time_us" => {
+                let time = value.parse::<u64>().unwrap_or(self.out_time_us);
+                let advanced = time > self.out_time_us;
+                self.out_time_us = time;
+                if advanced { ProgressLine::Advanced } else { ProgressLine::Other }
+            }
+            "speed" => {
+                self.speed = value.trim().trim_end_matches('x').parse::<f32>().ok();
+                ProgressLine::Other
+            }
+            "progress" => {
+                let progress = (self.out_time_us as f64 / 1_000_000.0 / duration).clamp(0.0, 1.0);
+                ProgressLine::BlockEnd(progress, self.speed)
+            }
+            _ => ProgressLine::Other,
+        }
+    }
+}
+
+// returns Err with a short reason when ffmpeg couldn't be started, failed, or the watchdog had to
+// stop a stalled encode
+fn run_ffmpeg(
+    args: &[String],
+    duration: f64,
+    config: &AppConfig,
+    log_tx: &Sender<String>,
+    current_child: &Mutex<Option<Child>>,
+    cancel_requested: &AtomicBool,
+) -> Result<(), String> {
+    // dump command string to the log for debugging
+    log_tx.send(format!("Running command: {}", command_line(args))).ok();
+
+    // run the command
+    let mut command = Command::new("ffmpeg");
+    command.args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if config.low_priority {
+        set_low_priority(&mut command);
+    }
+    // cores this machine doesn't have are dropped, none at all would leave ffmpeg unable to run
+    let cpu_affinity = config.cpu_affinity
+        .map(|mask| mask & utils::first_cores_mask(utils::cpu_core_count()))
+        .filter(|mask| *mask != 0);
+    if let Some(mask) = cpu_affinity {
+        set_cpu_affinity(&mut command, mask);
+    }
+    let mut cmd = match command.spawn() {
+        Ok(cmd) => cmd,
+        Err(err) => return Err(format!("could not run ffmpeg ({})", err)),
+    };
+    if let Some(mask) = cpu_affinity {
+        let message = match child_cpu_affinity(&cmd, mask) {
+            Some(true) => format!("Limiting ffmpeg to {} CPU cores.", mask.count_ones()),
+            Some(false) => "Couldn't limit ffmpeg to the chosen CPU cores, it runs on all of them.".to_string(),
+            None => "Limiting ffmpeg to some CPU cores isn't supported on this system, using all of them.".to_string(),
+        };
+        log_tx.send(message).ok();
+    }
+
+    // keep the child reachable so it can be killed while we read its output
+    let stdout = cmd.stdout.take().unwrap();
+    let stderr = cmd.stderr.take().unwrap();
+    {
+        let mut child = current_child.lock().unwrap();
+        if cancel_requested.load(Ordering::SeqCst) {
+            cmd.kill().ok();
+        }
+        *child = Some(cmd);
+    }
+    // ffmpeg's log is read on its own thread so neither pipe can fill up and stall
+    let stderr_tx = log_tx.clone();
+    let stderr_thread = thread::spawn(move || {
+        for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+            stderr_tx.send(line).ok();
+        }
+    });
+
+    let last_progress = Mutex::new(Instant::now());
+    let finished = AtomicBool::new(false);
+    let stalled = AtomicBool::new(false);
+    thread::scope(|scope| {
+        // a hung driver leaves ffmpeg alive but silent, so kill it once the output stops advancing
+        if let Some(timeout) = config.stall_timeout_secs.map(|secs| Duration::from_secs(secs.into())) {
+            let (last_progress, finished, stalled) = (&last_progress, &finished, &stalled);
+            scope.spawn(move || {
+                while !finished.load(Ordering::SeqCst) {
+                    thread::sleep(STALL_CHECK_INTERVAL);
+                    if last_progress.lock().unwrap().elapsed() < timeout {
+                        continue;
+                    }
+                    stalled.store(true, Ordering::SeqCst);
+                    if let Some(cmd) = current_child.lock().unwrap().as_mut() {
+                        cmd.kill().ok();
+                    }
+                    break;
+                }
+            });
+        }
+
+        let mut parser = ProgressParser::default();
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            match parser.feed(&line, duration) {
+                ProgressLine::Advanced => *last_progress.lock().unwrap() = Instant::now(),
+                ProgressLine::BlockEnd(progress, speed) => {
+                    log_tx.send(format!("[progress]:{}", progress)).ok();
+                    if let Some(speed) = speed {
+                        log_tx.send(format!("[speed]:{}", speed)).ok();
+                    }
+                }
+                ProgressLine::Other => {}
+            }
+        }
+        finished.store(true, Ordering::SeqCst);
+    });
+    stderr_thread.join().ok();
+    let status = current_child.lock().unwrap().take().and_then(|mut cmd| cmd.wait().ok());
+
+    if stalled.load(Ordering::SeqCst) {
+        let secs = config.stall_timeout_secs.unwrap_or_default();
+        log_tx.send(format!("No progress for {} seconds, stopped ffmpeg.", secs)).ok();
+        return Err(format!("stalled, no progress for {} s", secs));
+    }
+    // a killed ffmpeg exits with an error too, cancelling is handled by the caller
+    if cancel_requested.load(Ordering::SeqCst) {
+        return Ok(());
+    }
+    match status {
+        Some(status) if status.success() => Ok(()),
+        Some(status) => Err(format!("ffmpeg exited with {}", status)),
+        None => Err("lost track of ffmpeg before it finished".to_string()),
+    }
+}
+
+// runs a helper ffmpeg to the end, parked in current_child like run_ffmpeg so cancelling kills it.
+// returns whether it succeeded and its log, None when it couldn't start or was cancelled
+fn run_cancellable(args: &[String], current_child: &Mutex<Option<Child>>, cancel_requested: &AtomicBool) -> Option<(bool, String)> {
+    let mut cmd = Command::new("ffmpeg")
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .ok()?;
+    let stderr = cmd.stderr.take()?;
+    {
+        let mut child = current_child.lock().unwrap();
+        if cancel_requested.load(Ordering::SeqCst) {
+            cmd.kill().ok();
+        }
+        *child = Some(cmd);
+    }
+    let mut log = String::new();
+    BufReader::new(stderr).read_to_string(&mut log).ok();
+    let status = current_child.lock().unwrap().take()?.wait().ok()?;
+    (!cancel_requested.load(Ordering::SeqCst)).then_some((status.success(), log))
+}
+
+// remove whatever a failed encode left behind and report why it failed
+fn fail_outputs(output_files: &[PathBuf], reason: &str, log_tx: &Sender<String>) {
+    for path in output_files {
+        std::fs::remove_file(path).ok();
+    }
+    log_tx.send(format!("[failed]:{}", reason)).ok();
+}
+
+// ffmpeg writes here until it finishes, so a cut-off file never carries the finished name
+pub fn partial_path(output_path: &Path) -> PathBuf {
+    let mut path = output_path.as_os_str().to_owned();
+    path.push(".part");
+    PathBuf::from(path)
+}
+
+// the file ffmpeg writes to. a .part name hides the container from ffmpeg, so it's named with -f
+fn output_target_args(config: &AppConfig, output_path: &Path) -> Vec<String> {
+    if !config.atomic_output {
+        return vec!["-y".to_string(), ffmpeg_url(output_path)];
+    }
+    let muxer = match output_path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase).as_deref() {
+        Some("mkv") => "matroska",
+        Some("mov") => "mov",
+        _ => "mp4",
+    };
+    vec!["-f".to_string(), muxer.to_string(), "-y".to_string(), ffmpeg_url(&partial_path(output_path))]
+}
+
+// gives a successful encode's .part file the real name. a failed or cancelled one stays at .part, so
+// cleaning up never touches an earlier output with the real name. returns the file that's left
+fn publish_output(config: &AppConfig, output_path: &Path, result: Result<(), String>, cancel_requested: &AtomicBool) -> (PathBuf, Result<(), String>) {
+    if !config.atomic_output {
+        return (output_path.to_path_buf(), result);
+    }
+    let partial = partial_path(output_path);
+    if result.is_err() || cancel_requested.load(Ordering::SeqCst) {
+        return (partial, result);
+    }
+    match std::fs::rename(&partial, output_path) {
+        Ok(()) => (output_path.to_path_buf(), Ok(())),
+        Err(err) => (partial, Err(format!("couldn't rename the finished output: {}", err))),
+    }
+}
+
+// anything smaller can't hold a playable video, not even the container's headers
+const MIN_OUTPUT_BYTES: u64 = 1024;
+
+fn finish_outputs(
+    output_files: &[PathBuf],
+    result: Result<(), String>,
+    source_size_bytes: u64,
+    config: &AppConfig,
+    log_tx: &Sender<String>,
+    cancel_requested: &AtomicBool,
+) {
+    // clean up the partial output of a cancelled job and put it back in the queue
+    if cancel_requested.load(Ordering::SeqCst) {
+        for path in output_files {
+            std::fs::remove_file(path).ok();
+        }
+        log_tx.send("Compression cancelled.".to_string()).ok();
+        log_tx.send("[cancelled]".to_string()).ok();
+        return;
+    }
+    // a failed ffmpeg can still leave a sizeable file behind, it's never counted as done
+    if let Err(reason) = result {
+        fail_outputs(output_files, &reason, log_tx);
+        return;
+    }
+
+    // some filter errors end with a zero exit code and nothing written, which mustn't pass as done
+    let output_sizes: Vec<u64> = output_files.iter()
+        .filter_map(|p| std::fs::metadata(p).ok())
+        .map(|m| m.len())
+        .collect();
+    let smallest = output_sizes.iter().min().copied();
+    if output_sizes.len() < output_files.len() || smallest.is_none_or(|size| size < MIN_OUTPUT_BYTES) {
+        let reason = match smallest {
+            Some(size) if output_sizes.len() == output_files.len() => format!("ffmpeg finished but wrote only {}", utils::format_size(size)),
+            _ => "ffmpeg finished without writing an output".to_string(),
+        };
+        log_tx.send(format!("{}, check the log above for filter errors.", reason)).ok();
+        fail_outputs(output_files, &reason, log_tx);
+        return;
+    }
+
+    // check output file size, dropping outputs that failed to get any smaller
+    let size = output_sizes.iter().sum();
+    if output_sizes.len() > 1 {
+        log_tx.send(format!("Wrote {} parts.", output_sizes.len())).ok();
+    }
+    if config.discard_larger_output && size >= source_size_bytes {
+        for path in output_files {
+            std::fs::remove_file(path).ok();
+        }
+        log_tx.send(format!(
+            "Output ({}) is not smaller than the source ({}), discarded it.",
+            utils::format_size(size),
+            utils::format_size(source_size_bytes),
+        )).ok();
+        log_tx.send("[already_optimal]".to_string()).ok();
+    } else {
+        log_tx.send(format!("[output_size]:{}", size)).ok();
+    }
+}
+
+// <prefix><stem><suffix><tail> next to the input, or in a compressed/ folder beside it
+fn output_file_name(input: &Path, config: &AppConfig, tail: &str) -> PathBuf {
+    let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+    let prefix = utils::sanitize_file_name(&config.output_prefix);
+    let mut suffix = utils::sanitize_file_name(&config.output_suffix);
+
+    // never let an empty marker point the output back at an mp4 input
+    if prefix.is_empty() && suffix.is_empty() && !config.output_to_subfolder {
+        suffix = "_compressed".to_string();
+    }
+    let file_name = format!("{}{}{}{}", prefix, stem, suffix, tail);
+    if config.output_to_subfolder {
+        input.with_file_name(OUTPUT_SUBFOLDER).join(file_name)
+    } else {
+        input.with_file_name(file_name)
+    }
+}
+
+fn create_output_dir(output_path: &Path, log_tx: &Sender<String>) -> bool {
+    let Some(dir) = output_path.parent() else {
+        return true;
+    };
+    match std::fs::create_dir_all(dir) {
+        Ok(()) => true,
+        Err(err) => {
+            log_tx.send(format!("Failed to create output folder {}: {}", dir.display(), err)).ok();
+            false
+        }
+    }
+}
+
+// containers that can hold the h264/aac output, anything else falls back to mp4
+const MATCHABLE_CONTAINERS: [&str; 4] = ["mp4", "m4v", "mov", "mkv"];
+
+pub fn output_extension(input: &Path, config: &AppConfig) -> String {
+    let source_extension = input.extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .filter(|e| MATCHABLE_CONTAINERS.contains(&e.as_str()));
+    match source_extension {
+        Some(extension) if config.match_source_container => extension,
+        _ => "mp4".to_string(),
+    }
+}
+
+pub fn output_path_for(input: &Path, config: &AppConfig) -> PathBuf {
+    output_file_name(input, config, &format!(".{}", output_extension(input, config)))
+}
+
+// an mp4 only becomes readable once ffmpeg finishes it, so an unreadable output was cut off mid-encode
+pub fn is_incomplete_output(path: &Path) -> bool {
+    path.exists() && probe_media_info(path).map_or(true, |info| info.duration.is_none())
+}
+
+// ffmpeg segment muxer pattern, expanded to part000, part001, ...
+// a literal % in the file name would be read as part of the pattern, so it's doubled
+fn segment_output_pattern(input: &Path, config: &AppConfig) -> PathBuf {
+    let base = output_file_name(input, config, "").to_string_lossy().replace('%', "%%");
+    PathBuf::from(format!("{}.part%03d.{}", base, output_extension(input, config)))
+}
+
+fn segment_paths(input: &Path, config: &AppConfig) -> Vec<PathBuf> {
+    (0..)
+        .map(|i| output_file_name(input, config, &format!(".part{:03}.{}", i, output_extension(input, config))))
+        .take_while(|p| p.exists())
+        .collect()
+}
+
+// read input video file's parameters to calculate output file's parameters later
+// errors carry ffprobe's own stderr so the user can see why the probe failed.
+// values are matched by key since ffprobe's line order follows its sections, not -show_entries
+// the bitrate is 0 without audio and None when the container doesn't report it
+fn get_duration_and_audio_bitrate(path: &str) -> Result<(f64, Option<u32>), String> {
+    let output = Command::new("ffprobe")
+        .args([
+            "-v", "error",
+            "-select_streams", "a:0",
+            "-show_entries", "format=duration:stream=codec_type,bit_rate",
+            "-of", "default=noprint_wrappers=1",
+            path,
+        ])
+        .output()
+        .map_err(|e| format!("could not run ffprobe ({})", e))?;
+
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    if !output.status.success() {
+        return Err(if stderr.is_empty() {
+            format!("ffprobe exited with {}", output.status)
+        } else {
+            stderr
+        });
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut duration = None;
+    let mut has_audio = false;
+    let mut bitrate = None;
+    for (key, value) in stdout.lines().filter_map(|l| l.trim().split_once('=')) {
+        match key {
+            "duration" => duration = value.parse::<f64>().ok(),
+            "codec_type" => has_audio = value == "audio",
+            // N/A for containers like mkv that don't store per stream bitrates
+            "bit_rate" => bitrate = value.parse::<u32>().ok(),
+            _ => {}
+        }
+    }
+
+    let duration = duration.ok_or_else(|| if stderr.is_empty() {
+        format!("ffprobe reported no duration: {:?}", stdout.trim())
+    } else {
+        stderr.clone()
+    })?;
+    let bitrate = if has_audio { bitrate } else { Some(0) };
+
+    Ok((duration, bitrate))
+}
+
+// codecs and container for display, read from ffprobe's json output so streams stay paired with their types
+pub fn probe_media_info(path: &Path) -> Result<MediaInfo, String> {
+    let output = Command::new("ffprobe")
+        .args([
+            "-v", "error",
+            "-show_entries", "format=format_name,duration:stream=codec_type,codec_name,width,height,avg_frame_rate:stream_disposition=attached_pic",
+            "-of", "json",
+        ])
+        .arg(path)
+        .output()
+        .map_err(|e| format!("could not run ffprobe ({})", e))?;
+
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).map_err(|e| e.to_string())?;
+    let streams = json["streams"].as_array().cloned().unwrap_or_default();
+    let video_streams: Vec<VideoStream> = streams.iter()
+        .filter(|s| s["codec_type"] == "video")
+        .enumerate()
+        .map(|(index, s)| VideoStream {
+            index: index as u32,
+            codec: s["codec_name"].as_str().map(str::to_string),
+            width: s["width"].as_u64().map(|w| w as u32),
+            height: s["height"].as_u64().map(|h| h as u32),
+            cover_art: s["disposition"]["attached_pic"] == 1,
+        })
+        .collect();
+    // describe the stream that gets encoded by default, cover art can come first
+    let video_stream = streams.iter()
+        .filter(|s| s["codec_type"] == "video")
+        .find(|s| s["disposition"]["attached_pic"] != 1)
+        .or_else(|| streams.iter().find(|s| s["codec_type"] == "video"));
+    let codec_of = |codec_type: &str| streams.iter()
+        .find(|s| s["codec_type"] == codec_type && s["disposition"]["attached_pic"] != 1)
+        .and_then(|s| s["codec_name"].as_str())
+        .map(str::to_string);
+
+    // format_name lists every alias (e.g. "mov,mp4,m4a"), prefer the one matching the extension
+    let format_names: Vec<&str> = json["format"]["format_name"].as_str().unwrap_or_default().split(',').collect();
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_ascii_lowercase();
+    let container = format_names.iter()
+        .find(|name| **name == extension)
+        .or(format_names.first())
+        .map(|name| name.to_string())
+        .unwrap_or_default();
+
+    Ok(MediaInfo {
+        container,
+        video_codec: codec_of("video"),
+        audio_codec: codec_of("audio"),
+        width: video_stream.and_then(|s| s["width"].as_u64()).map(|w| w as u32),
+        height: video_stream.and_then(|s| s["height"].as_u64()).map(|h| h as u32),
+        frame_rate: video_stream.and_then(|s| s["avg_frame_rate"].as_str()).and_then(parse_frame_rate),
+        duration: json["format"]["duration"].as_str().and_then(|d| d.parse().ok()),
+        video_streams,
+    })
+}
+
+// average frame rate of the first video stream, reported by ffprobe as a fraction like 60000/1001
+fn get_frame_rate(path: &str) -> Option<f64> {
+    let output = Command::new("ffprobe")
+        .args([
+            "-v", "error",
+            "-select_streams", "v:0",
+            "-show_entries", "stream=avg_frame_rate",
+            "-of", "default=noprint_wrappers=1:nokey=1",
+            path,
+        ])
+        .output()
+        .ok()?;
+
+    parse_frame_rate(String::from_utf8_lossy(&output.stdout).trim())
+}
+
+fn parse_frame_rate(fraction: &str) -> Option<f64> {
+    let (num, den) = fraction.split_once('/')?;
+    let (num, den) = (num.parse::<f64>().ok()?, den.parse::<f64>().ok()?);
+    (den > 0.0).then(|| num / den)
+}
+
+// active encoder sessions summed over all nvidia gpus, None if nvidia-smi isn't available
+pub fn nvenc_sessions_in_use() -> Option<u32> {
+    let output = Command::new("nvidia-smi")
+        .args(["--query-gpu=encoder.stats.sessionCount", "--format=csv,noheader,nounits"])
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| line.trim().parse::<u32>().ok())
+        .sum()
+}
+
+fn has_chapters(path: &str) -> bool {
+    Command::new("ffprobe")
+        .args([
+            "-v", "error",
+            "-show_entries", "chapter=id",
+            "-of", "csv=p=0",
+            path,
+        ])
+        .output()
+        .is_ok_and(|output| output.status.success() && !output.stdout.trim_ascii().is_empty())
+}
+
+// size, duration and total bitrate are tied by size = bitrate * duration, each solves for one of them.
+// the bitrate only aims at SIZE_HEADROOM of the target, leaving room for container overhead and
+// rate control overshoot. e.g. 10 MB over 100 s is a 93,000 byte/s budget (744 kbps), 10 MiB is 97,517 byte/s
+const SIZE_HEADROOM: f64 = 0.93;
+
+// bounds for the learned overshoot factor, so one odd file can't wreck later targets
+pub const MIN_SIZE_CORRECTION: f64 = 0.5;
+pub const MAX_SIZE_CORRECTION: f64 = 2.0;
+
+// what a size-targeted encode aims for, None when the size isn't known up front. a manual bitrate
+// needs the duration, with audio assumed to be 128 kbps unless it's set too
+pub fn expected_output_size(config: &AppConfig, duration: Option<f64>) -> Option<u64> {
+    if !config.bitrate_mode.size_targeted() {
+        return None;
+    }
+    if let Some(video_kbps) = config.manual_video_bitrate_kbps {
+        let audio_kbps = config.manual_audio_bitrate_kbps.unwrap_or(128);
+        return Some(planned_size((video_kbps + audio_kbps) * 1000, duration?));
+    }
+    if config.quality_target_vmaf.is_some() {
+        return None;
+    }
+    Some((config.target_size_mb as f64 * config.size_unit.bytes_per_mb() * SIZE_HEADROOM) as u64)
+}
+
+pub fn bitrate_for_size(size_mb: f64, duration: f64, unit: SizeUnit) -> f64 {
+    size_mb * unit.bytes_per_mb() * 8.0 * SIZE_HEADROOM / duration
+}
+
+pub fn duration_for_size(size_mb: f64, bitrate: f64, unit: SizeUnit) -> f64 {
+    size_mb * unit.bytes_per_mb() * 8.0 * SIZE_HEADROOM / bitrate
+}
+
+pub fn size_for_bitrate(bitrate: f64, duration: f64, unit: SizeUnit) -> f64 {
+    bitrate * duration / (unit.bytes_per_mb() * 8.0 * SIZE_HEADROOM)
+}
+
+fn calculate_bitrate(config: &AppConfig, duration: f64, mut audio_bitrate: u32) -> Option<(u32, u32)> {
+    // a manual bitrate skips the target size entirely
+    if let Some(video_kbps) = config.manual_video_bitrate_kbps {
+        let audio_bitrate = config.manual_audio_bitrate_kbps.map_or(audio_bitrate, |kbps| kbps * 1000);
+        return Some((video_kbps * 1000, audio_bitrate));
+    }
+
+    // calculate the allowed bits per second to reach target output file size, scaled down by how much
+    // earlier encodes overshot their planned size
+    let target_total_bitrate = bitrate_for_size(config.target_size_mb as f64, duration, config.size_unit)
+        / config.size_correction.clamp(MIN_SIZE_CORRECTION, MAX_SIZE_CORRECTION);
+
+    // throttle audio bitrate if bandwidth is bad. copied audio or a quality setting can't be throttled, so
+    // their budget stays
+    if config.audio_quality.is_none() && !config.copy_audio && 10.0 * audio_bitrate as f64 > target_total_bitrate {
+        audio_bitrate = (target_total_bitrate / 10.0) as u32;
+        audio_bitrate = audio_bitrate.clamp(64_000, 256_000)
+    }
+
+    // allocate some bitrate for audio, spend the remaining bitrate on video
+    let video_bitrate = (target_total_bitrate as u32).saturating_sub(audio_bitrate);
+
+    Some((video_bitrate, audio_bitrate))
+}
+
+// audio codecs the mp4 family of containers can hold without re-encoding, mkv takes anything
+const MP4_AUDIO_CODECS: [&str; 6] = ["aac", "mp3", "ac3", "eac3", "alac", "opus"];
+
+// returns the settings to fall back to re-encoding with when the audio can't be copied, or the copied
+// stream's measured bitrate when its container doesn't report one
+fn without_uncopyable_audio(input: &Path, config: &AppConfig, log_tx: &Sender<String>) -> (Option<AppConfig>, Option<u32>) {
+    if !config.copy_audio {
+        return (None, None);
+    }
+    match copied_audio_bitrate(input, config) {
+        Ok(bitrate) => (None, bitrate),
+        Err(reason) => {
+            log_tx.send(format!("Not copying audio, {}. Re-encoding it to AAC instead.", reason)).ok();
+            (Some(AppConfig { copy_audio: false, ..config.clone() }), None)
+        }
+    }
+}
+
+// copying fails when the output container can't hold the source's audio codec, and the delay filter
+// needs decoded audio. a size target also needs the copied stream's real bitrate, which mkv doesn't
+// store, and lossless or surround tracks are far above the usual guess. Err says why it can't be copied
+fn copied_audio_bitrate(input: &Path, config: &AppConfig) -> Result<Option<u32>, String> {
+    if config.audio_delay_ms != 0 {
+        return Err("the audio delay needs it re-encoded".to_string());
+    }
+    // an unreadable source is left for the encode to report
+    let Some(codec) = probe_media_info(input).ok().and_then(|info| info.audio_codec) else {
+        return Ok(None);
+    };
+    let extension = output_extension(input, config);
+    if extension != "mkv" && !MP4_AUDIO_CODECS.contains(&codec.as_str()) {
+        return Err(format!("{} audio can't be stored in {}", codec, extension));
+    }
+    if !config.size_targeted() {
+        return Ok(None);
+    }
+    match get_duration_and_audio_bitrate(&ffmpeg_url(input)) {
+        Ok((duration, None)) => measure_audio_bitrate(input, duration)
+            .map(Some)
+            .ok_or("its bitrate is unknown, so the target size can't account for it".to_string()),
+        _ => Ok(None),
+    }
+}
+
+// mkvmerge tags streams with their bitrate, otherwise the audio packets are added up, which reads
+// through the whole file
+fn measure_audio_bitrate(input: &Path, duration: f64) -> Option<u32> {
+    let probe = |entries: &str| Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "a:0", "-show_entries", entries, "-of", "csv=p=0"])
+        .arg(input)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).into_owned());
+
+    let tagged = probe("stream_tags=BPS,BPS-eng")
+        .and_then(|tags| tags.split([',', '\n']).find_map(|tag| tag.trim().parse::<u32>().ok()));
+    if tagged.is_some() {
+        return tagged;
+    }
+    let bytes: u64 = probe("packet=size")?.lines().filter_map(|size| size.trim().parse::<u64>().ok()).sum();
+    (bytes > 0 && duration > 0.0).then(|| (bytes as f64 * 8.0 / duration) as u32)
+}
+
+// stereo bitrate planned per unit of AAC VBR quality. the encoder lands well under this on most
+// sources, but a variable size has to be budgeted at its high end to stay under the target
+const AUDIO_QUALITY_BUDGET: f64 = 160_000.0;
+
+// scales the source audio bitrate down for a lower sample rate or fewer channels, assuming a 48 kHz
+// stereo source. the saved bits go to the video instead
+fn audio_budget(config: &AppConfig, source_audio_bitrate: u32) -> u32 {
+    if config.copy_audio {
+        return source_audio_bitrate;
+    }
+    // a source without audio stays at 0 whatever the quality
+    let source_audio_bitrate = match config.audio_quality {
+        Some(quality) if source_audio_bitrate > 0 => (quality as f64 * AUDIO_QUALITY_BUDGET) as u32,
+        _ => source_audio_bitrate,
+    };
+    let channel_factor = config.audio_channels.map_or(1.0, |c| (c as f64 / 2.0).min(1.0));
+    let rate_factor = config.audio_sample_rate.map_or(1.0, |r| (r as f64 / 48_000.0).min(1.0));
+    (source_audio_bitrate as f64 * channel_factor * rate_factor) as u32
+}
+
+// plain language version of the numbers in the ffmpeg command
+fn log_bitrate_summary(config: &AppConfig, size_targeted: bool, video_bitrate: u32, audio_bitrate: u32, duration: f64, log_tx: &Sender<String>) {
+    if !config.bitrate_mode.size_targeted() {
+        log_tx.send(format!(
+            "Target: constant quality {}, Audio {}, Duration {}",
+            config.quality_level,
+            utils::format_bitrate(audio_bitrate),
+            utils::format_duration(duration),
+        )).ok();
+        return;
+    }
+    let target = match config.quality_target_vmaf {
+        _ if config.manual_video_bitrate_kbps.is_some() => "manual bitrate".to_string(),
+        Some(vmaf) if !size_targeted => format!("VMAF {:.1}", vmaf),
+        _ => format!("{} {}", config.target_size_mb, config.size_unit.label()),
+    };
+    log_tx.send(format!(
+        "Target: {}, Video ~{}, Audio {}{}, Duration {}",
+        target,
+        utils::format_bitrate(video_bitrate),
+        if config.copy_audio { "copied, ~" } else { "" },
+        utils::format_bitrate(audio_bitrate),
+        utils::format_duration(duration),
+    )).ok();
+}
+
+// bits per pixel per frame the settings leave for this source, after crop, scaling and frame rate
+// changes. audio is assumed to be 128 kbps since the probe doesn't read its bitrate
+pub fn estimate_quality(config: &AppConfig, info: &MediaInfo, trim: Option<Trim>) -> Option<(f64, QualityEstimate)> {
+    if !config.bitrate_mode.size_targeted() {
+        return None;
+    }
+    let duration = match trim {
+        Some(trim) => trim.end - trim.start,
+        None => info.duration?,
+    };
+    let (source_width, source_height) = (info.width?, info.height?);
+    let (mut width, mut height) = (source_width as f64, source_height as f64);
+    if let Some(crop) = &config.crop {
+        let crop = crop.fitted(source_width, source_height);
+        (width, height) = (crop.width as f64, crop.height as f64);
+    }
+    if let Some(resolution) = &config.resolution {
+        let mut target_height = resolution.output_height(height);
+        if config.no_upscale {
+            target_height = target_height.min(height);
+        }
+        width *= target_height / height;
+        height = target_height;
+    }
+    let fps = config.frame_rate.or(info.frame_rate)?;
+
+    let (video_bitrate, _) = calculate_bitrate(config, duration, 128_000)?;
+    let bpp = video_bitrate as f64 / (width * height * fps).max(1.0);
+    Some((bpp, QualityEstimate::from_bits_per_pixel(bpp)))
+}
+
+// the largest standard height below the current output that lifts the estimate out of Poor, or the
+// smallest one when none do. None when the output is already 480p or smaller
+pub fn resolution_for_quality(config: &AppConfig, info: &MediaInfo, trim: Option<Trim>) -> Option<Resolution> {
+    let source_height = info.height? as f64;
+    let source_height = match (&config.crop, info.width) {
+        (Some(crop), Some(width)) => crop.fitted(width, source_height as u32).height as f64,
+        _ => source_height,
+    };
+    let current_height = match &config.resolution {
+        Some(resolution) if config.no_upscale => resolution.output_height(source_height).min(source_height),
+        Some(resolution) => resolution.output_height(source_height),
+        None => source_height,
+    };
+    let candidates: Vec<Resolution> = [Resolution::R1080, Resolution::R720, Resolution::R480].into_iter()
+        .filter(|resolution| resolution.output_height(source_height) < current_height)
+        .collect();
+    let acceptable = candidates.iter().find(|resolution| {
+        let lowered = AppConfig { resolution: Some((*resolution).clone()), ..config.clone() };
+        estimate_quality(&lowered, info, trim).is_some_and(|(_, estimate)| estimate != QualityEstimate::Poor)
+    });
+    acceptable.or(candidates.last()).cloned()
+}
+
+fn planned_size(total_bitrate: u32, duration: f64) -> u64 {
+    (total_bitrate as f64 * duration / 8.0) as u64
+}
+
+// seconds of video that fit in one part at the given total bitrate, with a margin for bitrate spikes
+fn segment_duration(part_size_mb: u32, unit: SizeUnit, total_bitrate: u32) -> f64 {
+    0.95 * duration_for_size(part_size_mb as f64, total_bitrate.max(1) as f64, unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 10 MB or MiB over 100 s, with SIZE_HEADROOM taken off
+    #[test]
+    fn bitrate_for_size_uses_the_chosen_unit() {
+        assert_eq!(bitrate_for_size(10.0, 100.0, SizeUnit::Decimal).round(), 744_000.0);
+        assert_eq!(bitrate_for_size(10.0, 100.0, SizeUnit::Binary).round(), 780_141.0);
+    }
+
+    // the 128 kbps audio is over a tenth of the budget, so it's throttled down to that tenth
+    #[test]
+    fn calculate_bitrate_splits_the_unit_budget() {
+        let decimal = AppConfig { target_size_mb: 10, size_unit: SizeUnit::Decimal, ..AppConfig::default() };
+        let binary = AppConfig { size_unit: SizeUnit::Binary, ..decimal.clone() };
+        assert_eq!(calculate_bitrate(&decimal, 100.0, 128_000), Some((669_600, 74_400)));
+        assert_eq!(calculate_bitrate(&binary, 100.0, 128_000), Some((702_126, 78_014)));
+    }
+
+    // 10 MB at 1 Mbps is 74.4 s after SIZE_HEADROOM, each part takes another 5% off for spikes
+    #[test]
+    fn segment_duration_leaves_room_in_each_part() {
+        assert!((segment_duration(10, SizeUnit::Decimal, 1_000_000) - 70.68).abs() < 1e-9);
+        // a zero bitrate can't divide by zero
+        assert!(segment_duration(10, SizeUnit::Decimal, 0).is_finite());
+    }
+
+    #[test]
+    fn progress_parser_reads_blocks() {
+        let mut parser = ProgressParser::default();
+        assert_eq!(parser.feed("frame=120", 10.0), ProgressLine::Other);
+        assert_eq!(parser.feed("out_time_us=2500000", 10.0), ProgressLine::Advanced);
+        assert_eq!(parser.feed("speed=1.5x", 10.0), ProgressLine::Other);
+        assert_eq!(parser.feed("progress=continue", 10.0), ProgressLine::BlockEnd(0.25, Some(1.5)));
+        // a repeated or unreadable time isn't progress, and keeps the last one
+        assert_eq!(parser.feed("out_time_us=2500000", 10.0), ProgressLine::Other);
+        assert_eq!(parser.feed("out_time_us=N/A", 10.0), ProgressLine::Other);
+        assert_eq!(parser.feed("speed=N/A", 10.0), ProgressLine::Other);
+        assert_eq!(parser.feed("progress=continue", 10.0), ProgressLine::BlockEnd(0.25, None));
+        // past the planned duration still reads as done, not more
+        parser.feed("out_time_us=12000000", 10.0);
+        assert_eq!(parser.feed("progress=end", 10.0), ProgressLine::BlockEnd(1.0, None));
+    }
+
+    #[test]
+    fn ffmpeg_url_marks_relative_paths_that_look_like_protocols() {
+        assert_eq!(ffmpeg_url(Path::new("clip 1:30.mp4")), "file:clip 1:30.mp4");
+        assert_eq!(ffmpeg_url(Path::new("plain.mp4")), "plain.mp4");
+        // only the first component can be read as a protocol
+        assert_eq!(ffmpeg_url(Path::new("videos/1:30.mp4")), "videos/1:30.mp4");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn ffmpeg_url_keeps_absolute_paths() {
+        assert_eq!(ffmpeg_url(Path::new("/tmp/clip 1:30.mp4")), "/tmp/clip 1:30.mp4");
+    }
+
+    // picking any frame finds the whole run of numbers around it
+    #[test]
+    fn detect_image_sequence_finds_the_whole_run() {
+        let dir = std::env::temp_dir().join(format!("video_compressor_gui_test_{}_sequence", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for n in 1..=3 {
+            std::fs::write(dir.join(format!("frame_{:04}.png", n)), [0; 10]).unwrap();
+        }
+        std::fs::write(dir.join("cover.png"), [0; 10]).unwrap();
+
+        let sequence = detect_image_sequence(&dir.join("frame_0002.png"), 24);
+        let single = detect_image_sequence(&dir.join("cover.png"), 24);
+        std::fs::remove_file(dir.join("cover.png")).ok();
+        let from_folder = detect_image_sequence(&dir, 24).map(|s| s.first_frame);
+        std::fs::remove_dir_all(&dir).ok();
+
+        let sequence = sequence.unwrap();
+        assert_eq!(sequence.pattern, dir.join("frame_%04d.png"));
+        assert_eq!(sequence.first_frame, dir.join("frame_0001.png"));
+        assert_eq!((sequence.start_number, sequence.frame_count, sequence.size_bytes), (1, 3, 30));
+        assert_eq!(sequence.name_path(), dir.join("frame.png"));
+        assert!(single.is_none());
+        assert_eq!(from_folder, Some(dir.join("frame_0001.png")));
+    }
+}
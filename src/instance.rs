@@ -0,0 +1,114 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    io::{BufRead, BufReader, Write},
+    net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream},
+    path::PathBuf,
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::Duration,
+};
+
+use crate::app::PROGRAM_CONFIG_NAME;
+use crate::types::app::AppConfig;
+
+// a second launch hands its files to the window that's already open over a local socket, then exits.
+// the open window writes its port and a random token to a file beside the config that only this user
+// can read. the sender has to repeat the token, so other users and programs can't queue files, and the
+// reply starts with this line so a stale port now used by another program is never mistaken for us
+const HANDSHAKE: &str = "video-compressor-gui 1";
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+const REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+
+fn port_file() -> Option<PathBuf> {
+    let config_path = confy::get_configuration_file_path(PROGRAM_CONFIG_NAME, None).ok()?;
+    Some(config_path.parent()?.join("instance_port"))
+}
+
+pub fn enabled() -> bool {
+    confy::load::<AppConfig>(PROGRAM_CONFIG_NAME, None).map_or(true, |config| config.single_instance)
+}
+
+// true when a running instance took the files, the caller should exit then
+pub fn forward(files: &[PathBuf]) -> bool {
+    let Some(contents) = port_file().and_then(|path| std::fs::read_to_string(path).ok()) else {
+        return false;
+    };
+    let Some((Ok(port), token)) = contents.trim().split_once(' ').map(|(port, token)| (port.parse::<u16>(), token)) else {
+        return false;
+    };
+    let address = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    let Ok(mut stream) = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT) else {
+        return false;
+    };
+    stream.set_read_timeout(Some(REPLY_TIMEOUT)).ok();
+
+    // the other instance has its own working directory, so relative paths are resolved here
+    let mut message = format!("{} {}\n", HANDSHAKE, token);
+    for file in files {
+        let file = std::path::absolute(file).unwrap_or(file.clone());
+        message.push_str(&format!("{}\n", file.display()));
+    }
+    message.push('\n');
+    if stream.write_all(message.as_bytes()).is_err() {
+        return false;
+    }
+
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply).is_ok() && reply.trim() == HANDSHAKE
+}
+
+// starts accepting files from later launches. each message arrives as one batch, an empty batch is
+// a plain launch that only wants the window brought forward
+pub fn listen() -> Option<Receiver<Vec<PathBuf>>> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).ok()?;
+    let port = listener.local_addr().ok()?.port();
+    let port_file = port_file()?;
+    if let Some(dir) = port_file.parent() {
+        std::fs::create_dir_all(dir).ok()?;
+    }
+    let token = random_token();
+    write_private(&port_file, &format!("{} {}", port, token))?;
+    let expected = format!("{} {}", HANDSHAKE, token);
+
+    let (files_tx, files_rx) = mpsc::channel();
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            stream.set_read_timeout(Some(REPLY_TIMEOUT)).ok();
+            let Ok(mut reply_stream) = stream.try_clone() else {
+                continue;
+            };
+            let mut lines = BufReader::new(stream).lines().map_while(Result::ok);
+            if lines.next().as_deref() != Some(expected.as_str()) {
+                continue;
+            }
+            let files: Vec<PathBuf> = lines
+                .take_while(|line| !line.is_empty())
+                .map(PathBuf::from)
+                .collect();
+            if files_tx.send(files).is_err() {
+                return;
+            }
+            reply_stream.write_all(format!("{}\n", HANDSHAKE).as_bytes()).ok();
+        }
+    });
+    Some(files_rx)
+}
+
+// 128 bits from std's randomly keyed hasher, which is seeded by the OS
+fn random_token() -> String {
+    (0..2).map(|_| format!("{:016x}", RandomState::new().build_hasher().finish())).collect()
+}
+
+// replaces the file with one only this user can read. windows keeps the config folder private already
+fn write_private(path: &std::path::Path, contents: &str) -> Option<()> {
+    std::fs::remove_file(path).ok();
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path).ok()?.write_all(contents.as_bytes()).ok()
+}
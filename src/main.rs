@@ -1,15 +1,36 @@
 mod app;
+mod cli;
+mod encode;
+mod instance;
 mod utils;
 mod types;
 use app::MyApp;
 
-fn main() -> eframe::Result<()> {
+fn main() -> std::process::ExitCode {
+    // files alone, as passed by "open with" or a drop onto the app icon, open the window with them
+    // queued. any known option, e.g. --cli, runs the compressor headless instead
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if cli::requested(&args) {
+        return cli::run(args);
+    }
+    let initial_files: Vec<std::path::PathBuf> = args.into_iter()
+        .filter(|arg| arg != "--")
+        .map(std::path::PathBuf::from)
+        .collect();
+    if instance::enabled() && instance::forward(&initial_files) {
+        return std::process::ExitCode::SUCCESS;
+    }
+
     let native_options = eframe::NativeOptions::default();
-    eframe::run_native(
+    let result = eframe::run_native(
         "Video Compressor",
         native_options,
         Box::new(|_creation_context| {
-            Ok(Box::new(MyApp::load().unwrap()))
+            Ok(Box::new(MyApp::load(initial_files).unwrap()))
         })
-    )
+    );
+    match result {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(_) => std::process::ExitCode::FAILURE,
+    }
 }
@@ -1,42 +1,420 @@
-use std::path::{PathBuf};
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
 use serde::{Serialize, Deserialize};
 
-use crate::types::compression::{Encoder, Preset, Resolution};
+use crate::types::compression::{BitDepth, BitrateMode, ColorRange, ContentType, Crop, Encoder, ImageSequence, LogLevel, Preset, Profile, Resolution, ScaleAlgorithm, SizeUnit, SubtitleSource, Trim};
 
 // compression options
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct AppConfig {
     #[serde(default = "default_target_size")]
     pub target_size_mb: u32,
+    // new configs start at MB, ones saved before the setting existed keep the larger budget they had
+    #[serde(default = "legacy_size_unit")]
+    pub size_unit: SizeUnit,
+    // rolling average of actual / planned output size, divides the bitrate of later encodes
+    #[serde(default = "default_size_correction")]
+    pub size_correction: f64,
+    // completed encodes that went into size_correction
+    #[serde(default)]
+    pub size_correction_samples: u32,
 
-    pub frame_rate: Option<u32>,
+    // None keeps the source's frame rate. fractional so NTSC rates like 29.97 fit
+    pub frame_rate: Option<f64>,
+    // make up new frames with minterpolate when the frame rate is raised, instead of repeating frames
+    #[serde(default)]
+    pub interpolate_frames: bool,
+    // playback rate given to image sequences when they're added
+    #[serde(default = "default_sequence_fps")]
+    pub sequence_fps: u32,
     
     #[serde(default)]
     pub encoder: Encoder,
 
+    #[serde(default = "default_nvenc_session_limit")]
+    pub nvenc_session_limit: u32,
+
     #[serde(default)]
     pub dark_mode_enabled: bool,
+    // minimal window without the tabs
+    #[serde(default)]
+    pub compact_mode: bool,
+    // section headers between processing, waiting, finished and failed rows
+    #[serde(default)]
+    pub group_queue_by_status: bool,
+    // byte counts instead of KB/MB in the queue and summary, for checking against a hard limit
+    #[serde(default)]
+    pub exact_sizes: bool,
+    // start the most recently added waiting file next instead of the oldest
+    #[serde(default)]
+    pub newest_first: bool,
+    // sources added in earlier sessions, newest first. personal, so it's kept out of exports and
+    // per-file snapshots
+    #[serde(default)]
+    pub recent_files: Vec<PathBuf>,
+    // start ffmpeg at background priority so the machine stays responsive
+    #[serde(default)]
+    pub low_priority: bool,
+    // kill an encode that makes no progress for this long, None waits forever
+    #[serde(default = "default_stall_timeout")]
+    pub stall_timeout_secs: Option<u32>,
+    // cores ffmpeg may run on, bit n for core n. None uses every core
+    #[serde(default)]
+    pub cpu_affinity: Option<u64>,
+    // how often progress and log lines from a running encode reach the window
+    #[serde(default = "default_ui_refresh_hz")]
+    pub ui_refresh_hz: u32,
+    // where sample encodes and other scratch files go, None uses the system temp folder
+    #[serde(default)]
+    pub temp_dir: Option<PathBuf>,
 
     pub resolution: Option<Resolution>,
+    // keep sources that are already smaller than the chosen height at their own size
+    #[serde(default = "default_true")]
+    pub no_upscale: bool,
+    // fit inside the chosen height's 16:9 frame and fill the rest with black bars, for exact dimensions
+    #[serde(default)]
+    pub pad_to_fit: bool,
+
+    #[serde(default)]
+    pub scale_algorithm: ScaleAlgorithm,
+    // encoder tuning for what the video shows, files can pick their own in the queue
+    #[serde(default)]
+    pub content_type: ContentType,
+
+    pub crop: Option<Crop>,
+    // subtitles rendered into the picture
+    #[serde(default)]
+    pub burn_subtitles: Option<SubtitleSource>,
 
     #[serde(default)]
     pub preset: Preset,
+
+    #[serde(default)]
+    pub bitrate_mode: BitrateMode,
+    // level for BitrateMode::Crf, 0-51 with lower meaning better
+    #[serde(default = "default_quality_level")]
+    pub quality_level: u32,
+    #[serde(default)]
+    pub bit_depth: BitDepth,
+    // copy the source's color range, matrix, primaries and transfer tags so players decode colors the same way
+    #[serde(default = "default_true")]
+    pub preserve_color_tags: bool,
+    // tags the output with this range instead, for sources that are tagged wrong
+    #[serde(default)]
+    pub color_range: Option<ColorRange>,
+    // None leaves the profile and level to the encoder
+    #[serde(default)]
+    pub profile: Option<Profile>,
+    #[serde(default)]
+    pub level: Option<String>,
+
+    #[serde(default)]
+    pub discard_larger_output: bool,
+
+    #[serde(default)]
+    pub faststart: bool,
+    // encode to <output>.part and rename it once ffmpeg succeeds, so an interrupted encode never looks finished
+    #[serde(default = "default_true")]
+    pub atomic_output: bool,
+
+    pub split_part_size_mb: Option<u32>,
+
+    #[serde(default)]
+    pub check_for_updates: bool,
+    // launching again adds the files to the open window instead of opening a second one
+    #[serde(default = "default_true")]
+    pub single_instance: bool,
+    // chime once the whole queue is through
+    #[serde(default)]
+    pub completion_sound: bool,
+
+    #[serde(default)]
+    pub log_level: LogLevel,
+
+    #[serde(default)]
+    pub keep_chapters: bool,
+
+    #[serde(default)]
+    pub auto_reduce_fps: bool,
+
+    #[serde(default)]
+    pub output_prefix: String,
+
+    #[serde(default = "default_output_suffix")]
+    pub output_suffix: String,
+    // write into a compressed/ folder next to each source instead of beside it
+    #[serde(default)]
+    pub output_to_subfolder: bool,
+    // keep mkv/mov/m4v sources in their container instead of writing mp4
+    #[serde(default)]
+    pub match_source_container: bool,
+    // container title tag, Some("") uses the source file name
+    #[serde(default)]
+    pub metadata_title: Option<String>,
+    #[serde(default)]
+    pub metadata_comment: String,
+    // when set, search for the smallest bitrate that keeps this VMAF score instead of using the target size
+    #[serde(default)]
+    pub quality_target_vmaf: Option<f32>,
+    // AAC variable bitrate quality (-q:a, 0.1-2) instead of a fixed audio bitrate
+    #[serde(default)]
+    pub audio_quality: Option<f32>,
+    // passes the source audio through untouched, its bitrate comes out of the size budget as is
+    #[serde(default)]
+    pub copy_audio: bool,
+    // None keeps the source's sample rate / channel layout
+    #[serde(default)]
+    pub audio_sample_rate: Option<u32>,
+    #[serde(default)]
+    pub audio_channels: Option<u32>,
+    // positive values play the audio later than the video
+    #[serde(default)]
+    pub audio_delay_ms: i32,
+    // bitrates used as is instead of deriving them from the target size, audio None keeps the usual budget
+    #[serde(default)]
+    pub manual_video_bitrate_kbps: Option<u32>,
+    #[serde(default)]
+    pub manual_audio_bitrate_kbps: Option<u32>,
+}
+
+impl AppConfig {
+    // the encode settings alone, for a file's own snapshot or a settings file meant for sharing
+    pub fn without_history(&self) -> AppConfig {
+        AppConfig { recent_files: Vec::new(), ..self.clone() }
+    }
+
+    // one line overview of the options that shape the output, e.g. "10 MB, CPU, ABR, 720p, 30 fps"
+    pub fn summary(&self) -> String {
+        let mut parts = vec![match (self.quality_target_vmaf, self.manual_video_bitrate_kbps) {
+            _ if !self.bitrate_mode.size_targeted() => format!("quality {}", self.quality_level),
+            (_, Some(kbps)) => format!("{} kbps", kbps),
+            (Some(vmaf), None) => format!("VMAF {:.1}", vmaf),
+            (None, None) => format!("{} {}", self.target_size_mb, self.size_unit.label()),
+        }];
+        parts.push(match self.encoder {
+            Encoder::CpuX264 => "CPU".to_string(),
+            Encoder::GpuNvenc => "GPU".to_string(),
+        });
+        if self.bitrate_mode.size_targeted() {
+            parts.push(self.bitrate_mode.label().to_string());
+        }
+        if self.bit_depth == BitDepth::Ten {
+            parts.push("10-bit".to_string());
+        }
+        match (&self.profile, &self.level) {
+            (Some(profile), Some(level)) => parts.push(format!("{}@{}", profile.as_str(), level)),
+            (Some(profile), None) => parts.push(format!("{} profile", profile.as_str())),
+            (None, Some(level)) => parts.push(format!("level {}", level)),
+            (None, None) => {}
+        }
+        if let Some(resolution) = &self.resolution {
+            parts.push(resolution.to_string());
+        }
+        if let Some(fps) = self.frame_rate {
+            parts.push(format!("{} fps{}", fps, if self.interpolate_frames { " interpolated" } else { "" }));
+        }
+        if self.crop.is_some() {
+            parts.push("cropped".to_string());
+        }
+        if self.burn_subtitles.is_some() {
+            parts.push("burned-in subtitles".to_string());
+        }
+        if let Some(preset) = self.preset.as_str() {
+            parts.push(format!("{} preset", preset));
+        }
+        if self.content_type != ContentType::General {
+            parts.push(format!("{} tuning", self.content_type.label().to_lowercase()));
+        }
+        if self.copy_audio {
+            parts.push("audio copied".to_string());
+        } else if let Some(quality) = self.audio_quality {
+            parts.push(format!("audio q{:.2}", quality));
+        }
+        if self.audio_channels == Some(1) {
+            parts.push("mono".to_string());
+        }
+        if let Some(sample_rate) = self.audio_sample_rate {
+            parts.push(format!("{} kHz", sample_rate as f64 / 1000.0));
+        }
+        if let Some(part_size_mb) = self.split_part_size_mb {
+            parts.push(format!("split at {} {}", part_size_mb, self.size_unit.label()));
+        }
+        parts.join(", ")
+    }
+
+    // whether the bitrate is worked out from the target size, rather than a quality level or a manual bitrate
+    pub fn size_targeted(&self) -> bool {
+        self.bitrate_mode.size_targeted() && self.manual_video_bitrate_kbps.is_none()
+    }
 }
 
 fn default_target_size() -> u32 {
     10
 }
 
+fn default_true() -> bool {
+    true
+}
+
+fn default_sequence_fps() -> u32 {
+    30
+}
+
+fn legacy_size_unit() -> SizeUnit {
+    SizeUnit::Binary
+}
+
+fn default_size_correction() -> f64 {
+    1.0
+}
+
+fn default_quality_level() -> u32 {
+    23
+}
+
+fn default_output_suffix() -> String {
+    "_compressed".to_string()
+}
+
+fn default_stall_timeout() -> Option<u32> {
+    Some(120)
+}
+
+fn default_ui_refresh_hz() -> u32 {
+    4
+}
+
+fn default_nvenc_session_limit() -> u32 {
+    3
+}
+
 impl ::std::default::Default for AppConfig {
     fn default() -> Self {
         Self {
             target_size_mb: 10,
+            size_unit: SizeUnit::Decimal,
+            size_correction: default_size_correction(),
+            size_correction_samples: 0,
             frame_rate: None,
+            interpolate_frames: false,
+            sequence_fps: default_sequence_fps(),
             encoder: Encoder::CpuX264,
+            nvenc_session_limit: 3,
             dark_mode_enabled: false,
+            compact_mode: false,
+            group_queue_by_status: false,
+            exact_sizes: false,
+            newest_first: false,
+            recent_files: Vec::new(),
+            low_priority: false,
+            stall_timeout_secs: default_stall_timeout(),
+            cpu_affinity: None,
+            ui_refresh_hz: default_ui_refresh_hz(),
+            temp_dir: None,
             resolution: None,
+            no_upscale: true,
+            pad_to_fit: false,
+            scale_algorithm: ScaleAlgorithm::None,
+            content_type: ContentType::General,
+            crop: None,
+            burn_subtitles: None,
             preset: Preset::None,
+            bitrate_mode: BitrateMode::Abr,
+            quality_level: default_quality_level(),
+            bit_depth: BitDepth::Eight,
+            preserve_color_tags: true,
+            color_range: None,
+            profile: None,
+            level: None,
+            discard_larger_output: false,
+            faststart: false,
+            atomic_output: true,
+            split_part_size_mb: None,
+            check_for_updates: false,
+            single_instance: true,
+            completion_sound: false,
+            log_level: LogLevel::Info,
+            keep_chapters: false,
+            auto_reduce_fps: false,
+            output_prefix: String::new(),
+            output_suffix: default_output_suffix(),
+            output_to_subfolder: false,
+            match_source_container: false,
+            metadata_title: None,
+            metadata_comment: String::new(),
+            quality_target_vmaf: None,
+            audio_quality: None,
+            copy_audio: false,
+            audio_sample_rate: None,
+            audio_channels: None,
+            audio_delay_ms: 0,
+            manual_video_bitrate_kbps: None,
+            manual_audio_bitrate_kbps: None,
+        }
+    }
+}
+
+// what ffprobe reports about a source file
+#[derive(Clone, Default)]
+pub struct MediaInfo {
+    pub container: String,
+    pub video_codec: Option<String>,
+    pub audio_codec: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub frame_rate: Option<f64>,
+    pub duration: Option<f64>,
+    // every video stream, including cover art that containers list as video
+    pub video_streams: Vec<VideoStream>,
+}
+
+#[derive(Clone)]
+pub struct VideoStream {
+    // position among the video streams, as in -map 0:v:N
+    pub index: u32,
+    pub codec: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    // a still image attached to the file, encoding it gives a one frame video
+    pub cover_art: bool,
+}
+
+impl VideoStream {
+    // e.g. "#1 1920x1080 h264"
+    pub fn label(&self) -> String {
+        let mut label = format!("#{}", self.index);
+        if let (Some(width), Some(height)) = (self.width, self.height) {
+            label.push_str(&format!(" {}x{}", width, height));
+        }
+        if let Some(codec) = &self.codec {
+            label.push_str(&format!(" {}", codec));
+        }
+        if self.cover_art {
+            label.push_str(" (cover art)");
         }
+        label
+    }
+}
+
+impl MediaInfo {
+    // the first stream that is real video rather than cover art
+    pub fn default_video_stream(&self) -> Option<u32> {
+        self.video_streams.iter().find(|s| !s.cover_art).map(|s| s.index)
+    }
+
+    // compact label like "H.264/AAC mp4"
+    pub fn summary(&self) -> String {
+        let codecs: Vec<String> = [&self.video_codec, &self.audio_codec]
+            .into_iter()
+            .flatten()
+            .map(|codec| match codec.as_str() {
+                "h264" => "H.264".to_string(),
+                "hevc" => "H.265".to_string(),
+                other => other.to_uppercase(),
+            })
+            .collect();
+        format!("{} {}", codecs.join("/"), self.container).trim().to_string()
     }
 }
 
@@ -45,12 +423,86 @@ pub enum FileStatus {
     Waiting,
     Processing,
     Done,
+    AlreadyOptimal,
+    // the reason is shown on hover
+    Failed(String),
 }
 
 #[derive(Clone)]
 pub struct QueueItem {
+    pub id: u64,
     pub path: PathBuf,
     pub status: FileStatus,
     pub size_bytes: u64,
     pub output_size_bytes: Option<u64>,
-}
\ No newline at end of file
+    pub progress: Option<f32>,
+    pub speed: Option<f32>,
+    // what the job is doing when it isn't the main encode, e.g. a quality search
+    pub stage: Option<String>,
+    pub media_info: Option<MediaInfo>,
+    // modification time seen by the last probe, used to notice the source being replaced
+    pub modified: Option<SystemTime>,
+    pub changed_on_disk: bool,
+    // free text tag like "needs crop", empty when unset
+    pub label: String,
+    // set when path is a numbered image pattern rather than a video
+    pub image_sequence: Option<ImageSequence>,
+    // part of the file to keep, None keeps all of it
+    pub trim: Option<Trim>,
+    // video stream picked by the user, None uses MediaInfo::default_video_stream
+    pub video_stream: Option<u32>,
+    // overrides the content type from the settings for this file
+    pub content_type: Option<ContentType>,
+    // when the last encode of this item started and how long it took
+    pub started_at: Option<Instant>,
+    pub elapsed: Option<Duration>,
+    pub encoder_used: Option<Encoder>,
+    // the main ffmpeg command of the last encode, as a shell line
+    pub ffmpeg_command: Option<String>,
+    // whether the current run picks this item up, cleared for unchecked items by Start Selected
+    pub eligible: bool,
+    // unfinished output from an earlier run that crashed or was killed
+    pub leftover_output: Option<PathBuf>,
+    // settings snapshot that takes precedence over the global config
+    pub settings_override: Option<AppConfig>,
+}
+
+// an unfinished queue item as it's saved between runs, the rest is probed again on load
+#[derive(Serialize, Deserialize)]
+pub struct SavedQueueItem {
+    // the first frame for an image sequence, which add_path turns back into the sequence
+    pub path: PathBuf,
+    #[serde(default)]
+    pub label: String,
+    #[serde(default)]
+    pub trim: Option<Trim>,
+    #[serde(default)]
+    pub video_stream: Option<u32>,
+    #[serde(default)]
+    pub content_type: Option<ContentType>,
+    #[serde(default)]
+    pub settings_override: Option<AppConfig>,
+}
+
+impl QueueItem {
+    // seconds of video the encode goes through, after trimming
+    pub fn encoded_duration(&self) -> Option<f64> {
+        match (self.trim, &self.image_sequence) {
+            (Some(trim), _) => Some(trim.end - trim.start),
+            (None, Some(sequence)) => Some(sequence.duration()),
+            (None, None) => self.media_info.as_ref().and_then(|i| i.duration),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn configs_without_a_size_unit_keep_mib() {
+        let old: AppConfig = serde_json::from_str(r#"{"target_size_mb": 8}"#).unwrap();
+        assert!(old.size_unit == SizeUnit::Binary);
+        assert!(AppConfig::default().size_unit == SizeUnit::Decimal);
+    }
+}
@@ -1,15 +1,225 @@
+use std::path::PathBuf;
 use serde::{Serialize, Deserialize};
 
 // ffmpeg encoder parameter
-#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default)]
 pub enum Encoder {
+    #[default]
     CpuX264,
     GpuNvenc,
 }
 
-impl Default for Encoder {
-    fn default() -> Self {
-        Encoder::CpuX264
+// ffmpeg -v level
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default)]
+pub enum LogLevel {
+    Quiet,
+    Error,
+    Warning,
+    #[default]
+    Info,
+}
+
+impl LogLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Quiet => "quiet",
+            LogLevel::Error => "error",
+            LogLevel::Warning => "warning",
+            LogLevel::Info => "info",
+        }
+    }
+}
+
+// what "MB" means for the target size. upload limits are stated either way: Discord counts
+// in MiB (its 10 MB limit is 10,485,760 bytes), Windows Explorer also shows MiB labelled as MB,
+// while macOS Finder and most storage vendors use decimal MB
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum SizeUnit {
+    // 1 MB = 1,000,000 bytes
+    #[default]
+    Decimal,
+    // 1 MiB = 1,048,576 bytes
+    Binary,
+}
+
+impl SizeUnit {
+    pub fn bytes_per_mb(&self) -> f64 {
+        match self {
+            SizeUnit::Decimal => 1000.0 * 1000.0,
+            SizeUnit::Binary => 1024.0 * 1024.0,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SizeUnit::Decimal => "MB",
+            SizeUnit::Binary => "MiB",
+        }
+    }
+}
+
+// upload limits of common services, for picking a target by where the file is going
+#[derive(Clone, Copy, PartialEq)]
+pub enum ServiceTarget {
+    Discord,
+    Gmail,
+    WhatsApp,
+    Imgur,
+}
+
+impl ServiceTarget {
+    pub const ALL: [ServiceTarget; 4] = [ServiceTarget::Discord, ServiceTarget::Gmail, ServiceTarget::WhatsApp, ServiceTarget::Imgur];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ServiceTarget::Discord => "Discord",
+            ServiceTarget::Gmail => "Gmail",
+            ServiceTarget::WhatsApp => "WhatsApp",
+            ServiceTarget::Imgur => "Imgur",
+        }
+    }
+
+    // the documented limit in the unit the service counts in. gmail's 25 MB covers the encoded
+    // message, which is about a third larger than the attachment
+    pub fn limit(&self) -> (u32, SizeUnit) {
+        match self {
+            ServiceTarget::Discord => (10, SizeUnit::Binary),
+            ServiceTarget::Gmail => (18, SizeUnit::Decimal),
+            ServiceTarget::WhatsApp => (16, SizeUnit::Decimal),
+            ServiceTarget::Imgur => (200, SizeUnit::Decimal),
+        }
+    }
+
+    // target size in the given unit, 5% under the limit and rounded down
+    pub fn target_size(&self, unit: SizeUnit) -> u32 {
+        let (limit, limit_unit) = self.limit();
+        (limit as f64 * limit_unit.bytes_per_mb() * 0.95 / unit.bytes_per_mb()) as u32
+    }
+}
+
+// output bit depth. 10-bit cuts banding at low bitrates but many phones, browsers and hardware
+// decoders can't play 10-bit H.264
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum BitDepth {
+    #[default]
+    Eight,
+    Ten,
+}
+
+impl Encoder {
+    // h264_nvenc has no 10-bit H.264 mode
+    pub fn supports(&self, bit_depth: BitDepth) -> bool {
+        bit_depth == BitDepth::Eight || *self == Encoder::CpuX264
+    }
+
+    // constant quality flags for a 0-51 level, lower is better. both scales line up closely
+    // enough that the same level looks about the same on either encoder
+    pub fn quality_args(&self, level: u32) -> Vec<String> {
+        let level = level.to_string();
+        match self {
+            Encoder::CpuX264 => vec!["-crf".to_string(), level],
+            // nvenc ignores -crf, and only honours -cq in vbr mode without a bitrate cap
+            Encoder::GpuNvenc => ["-rc", "vbr", "-cq", &level, "-b:v", "0"].map(String::from).to_vec(),
+        }
+    }
+}
+
+// rough picture quality to expect from the bits per pixel per frame of an H.264 encode
+#[derive(Clone, Copy, PartialEq)]
+pub enum QualityEstimate {
+    Poor,
+    Fair,
+    Good,
+    Excellent,
+}
+
+impl QualityEstimate {
+    pub fn from_bits_per_pixel(bpp: f64) -> Self {
+        match bpp {
+            b if b < 0.02 => QualityEstimate::Poor,
+            b if b < 0.05 => QualityEstimate::Fair,
+            b if b < 0.1 => QualityEstimate::Good,
+            _ => QualityEstimate::Excellent,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            QualityEstimate::Poor => "Poor",
+            QualityEstimate::Fair => "Fair",
+            QualityEstimate::Good => "Good",
+            QualityEstimate::Excellent => "Excellent",
+        }
+    }
+}
+
+// rate control mode. the aliases read configs saved before the modes were renamed
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum BitrateMode {
+    // average bitrate, varying around the target
+    #[default]
+    #[serde(alias = "Vbr")]
+    Abr,
+    Cbr,
+    // fixed quality level, the output size follows the content instead of the target
+    #[serde(alias = "Quality")]
+    Crf,
+    // an analysis pass first, so the bits go where the video needs them
+    TwoPassAbr,
+}
+
+impl BitrateMode {
+    // whether the bitrate is planned from the target size
+    pub fn size_targeted(&self) -> bool {
+        *self != BitrateMode::Crf
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            BitrateMode::Abr => "ABR",
+            BitrateMode::Cbr => "CBR",
+            BitrateMode::Crf => "CRF",
+            BitrateMode::TwoPassAbr => "2-pass ABR",
+        }
+    }
+
+    // x264 needs a separate ffmpeg run per pass, nvenc does both inside one
+    pub fn separate_passes(&self, encoder: &Encoder) -> bool {
+        *self == BitrateMode::TwoPassAbr && *encoder == Encoder::CpuX264
+    }
+
+    // rate control flags for the encoder. a separate-pass encode also needs -pass and -passlogfile,
+    // which are added per run
+    pub fn encoder_args(&self, encoder: &Encoder, video_bitrate: u32, quality_level: u32) -> Vec<String> {
+        let b_v = video_bitrate.to_string();
+        let mut args: Vec<String> = match self {
+            BitrateMode::Crf => return encoder.quality_args(quality_level),
+            BitrateMode::Abr | BitrateMode::TwoPassAbr => ["-b:v", &b_v].map(String::from).to_vec(),
+            // pin min/max rate to the target and use a one second buffer for strict CBR
+            BitrateMode::Cbr => ["-b:v", &b_v, "-minrate", &b_v, "-maxrate", &b_v, "-bufsize", &b_v].map(String::from).to_vec(),
+        };
+        match (self, encoder) {
+            (BitrateMode::Cbr, Encoder::GpuNvenc) => args.extend(["-rc", "cbr"].map(String::from)),
+            (BitrateMode::TwoPassAbr, Encoder::GpuNvenc) => args.extend(["-multipass", "fullres"].map(String::from)),
+            _ => {}
+        }
+        args
+    }
+}
+
+// yuv value range, limited (16-235) is what nearly all video uses, full (0-255) comes from some phones and screen recorders
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum ColorRange {
+    Limited,
+    Full,
+}
+
+impl ColorRange {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ColorRange::Limited => "tv",
+            ColorRange::Full => "pc",
+        }
     }
 }
 
@@ -19,14 +229,39 @@ pub enum Resolution {
     R1080,
     R720,
     R480,
+    // percent of the source's size, so mixed batches are all shrunk alike
+    Percent(u32),
 }
 
 impl Resolution {
-    pub fn to_height(&self) -> u32 {
+    // width:height for the scale filter. a percentage keeps both sides even, which yuv420p needs.
+    // with no_upscale a fixed height is capped at the source's, quoted so the comma survives the filter graph
+    pub fn to_scale_size(&self, no_upscale: bool) -> String {
+        match self {
+            Resolution::Percent(percent) => {
+                format!("trunc(iw*{f}/2)*2:trunc(ih*{f}/2)*2", f = *percent as f64 / 100.0)
+            }
+            _ if no_upscale => format!("-2:'min(ih,{})'", self.output_height(0.0)),
+            _ => format!("-1:{}", self.output_height(0.0)),
+        }
+    }
+
+    // width:height of the 16:9 frame a fixed height pads to, None for a percentage
+    pub fn canvas_size(&self) -> Option<(u32, u32)> {
+        match self {
+            Resolution::R1080 => Some((1920, 1080)),
+            Resolution::R720 => Some((1280, 720)),
+            Resolution::R480 => Some((854, 480)),
+            Resolution::Percent(_) => None,
+        }
+    }
+
+    pub fn output_height(&self, source_height: f64) -> f64 {
         match self {
-            Resolution::R1080 => 1080,
-            Resolution::R720 => 720,
-            Resolution::R480 => 480,
+            Resolution::R1080 => 1080.0,
+            Resolution::R720 => 720.0,
+            Resolution::R480 => 480.0,
+            Resolution::Percent(percent) => source_height * *percent as f64 / 100.0,
         }
     }
 }
@@ -37,13 +272,179 @@ impl std::fmt::Display for Resolution {
             Resolution::R1080 => write!(f, "1080p"),
             Resolution::R720 => write!(f, "720p"),
             Resolution::R480 => write!(f, "480p"),
+            Resolution::Percent(percent) => write!(f, "{}% size", percent),
         }
     }
 }
 
-// https://trac.ffmpeg.org/wiki/Encode/H.264#Preset
+// scale filter interpolation, None leaves ffmpeg's default (bicubic)
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default)]
+pub enum ScaleAlgorithm {
+    #[default]
+    None,
+    Bilinear,
+    Bicubic,
+    Lanczos,
+    Spline,
+}
+
+impl ScaleAlgorithm {
+    pub fn as_str(&self) -> Option<&'static str> {
+        match self {
+            ScaleAlgorithm::None => None,
+            ScaleAlgorithm::Bilinear => Some("bilinear"),
+            ScaleAlgorithm::Bicubic => Some("bicubic"),
+            ScaleAlgorithm::Lanczos => Some("lanczos"),
+            ScaleAlgorithm::Spline => Some("spline"),
+        }
+    }
+}
+
+// what the video shows, picks encoder tuning suited to it. General keeps the encoder's defaults
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum ContentType {
+    #[default]
+    General,
+    Film,
+    Animation,
+    ScreenCapture,
+    Grainy,
+}
+
+impl ContentType {
+    pub const ALL: [ContentType; 5] = [
+        ContentType::General,
+        ContentType::Film,
+        ContentType::Animation,
+        ContentType::ScreenCapture,
+        ContentType::Grainy,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ContentType::General => "General",
+            ContentType::Film => "Film",
+            ContentType::Animation => "Animation",
+            ContentType::ScreenCapture => "Screen capture",
+            ContentType::Grainy => "Grainy",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            ContentType::General => "No tuning, the encoder's defaults",
+            ContentType::Film => "Live action footage",
+            ContentType::Animation => "Cartoons and anime, flat areas of color get more B-frames",
+            ContentType::ScreenCapture => "Mostly static screens like tutorials and slideshows, keyframes are spaced further apart",
+            ContentType::Grainy => "Keeps film grain and noise instead of smoothing it into blotches",
+        }
+    }
+
+    // nvenc has no -tune for content, so it gets the closest of its own options
+    pub fn encoder_args(&self, encoder: &Encoder) -> &'static [&'static str] {
+        match (self, encoder) {
+            (ContentType::General, _) => &[],
+            (ContentType::Film, Encoder::CpuX264) => &["-tune", "film"],
+            (ContentType::Film, Encoder::GpuNvenc) => &[],
+            (ContentType::Animation, Encoder::CpuX264) => &["-tune", "animation", "-bf", "5"],
+            (ContentType::Animation, Encoder::GpuNvenc) => &["-bf", "4"],
+            (ContentType::ScreenCapture, Encoder::CpuX264) => &["-tune", "stillimage", "-g", "600"],
+            (ContentType::ScreenCapture, Encoder::GpuNvenc) => &["-g", "600"],
+            (ContentType::Grainy, Encoder::CpuX264) => &["-tune", "grain"],
+            (ContentType::Grainy, Encoder::GpuNvenc) => &["-spatial-aq", "1"],
+        }
+    }
+}
+
+// part of the input to keep, in seconds
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct Trim {
+    pub start: f64,
+    pub end: f64,
+}
+
+// the part of a source file that gets encoded
+#[derive(Clone, Copy, Default)]
+pub struct SourcePart {
+    pub trim: Option<Trim>,
+    // index among the video streams, None picks the first one that isn't cover art
+    pub video_stream: Option<u32>,
+}
+
+// where burned-in subtitles come from
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub enum SubtitleSource {
+    // nth subtitle stream of the input
+    Embedded(u32),
+    // .srt/.ass/.vtt file
+    External(PathBuf),
+}
+
+// crop rectangle in source pixels, applied before any scaling
 #[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct Crop {
+    pub width: u32,
+    pub height: u32,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl Crop {
+    // the whole frame, the starting point when cropping is turned on
+    pub fn full(width: u32, height: u32) -> Self {
+        Self { width, height, x: 0, y: 0 }
+    }
+
+    // the part of this rectangle that lies inside a frame of the given size, moved in rather than
+    // shrunk when only the offset overshoots
+    pub fn fitted(&self, width: u32, height: u32) -> Self {
+        let (w, h) = (self.width.min(width), self.height.min(height));
+        Self { width: w, height: h, x: self.x.min(width - w), y: self.y.min(height - h) }
+    }
+
+    // ffmpeg fits it the same way, since one setting applies to sources of any size
+    pub fn to_filter(&self) -> String {
+        format!(
+            "crop='min(iw,{})':'min(ih,{})':'min({},iw-ow)':'min({},ih-oh)'",
+            self.width, self.height, self.x, self.y,
+        )
+    }
+}
+
+// H.264 profile for players that only accept a subset of the format, e.g. old TVs
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum Profile {
+    Baseline,
+    Main,
+    High,
+}
+
+impl Profile {
+    pub fn from_name(name: &str) -> Option<Profile> {
+        match name {
+            "baseline" => Some(Profile::Baseline),
+            "main" => Some(Profile::Main),
+            "high" => Some(Profile::High),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Profile::Baseline => "baseline",
+            Profile::Main => "main",
+            Profile::High => "high",
+        }
+    }
+}
+
+// levels both libx264 and h264_nvenc accept, higher levels allow larger frames and bitrates
+pub const H264_LEVELS: [&str; 8] = ["3.0", "3.1", "3.2", "4.0", "4.1", "4.2", "5.0", "5.1"];
+
+// https://trac.ffmpeg.org/wiki/Encode/H.264#Preset
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default)]
 pub enum Preset {
+    #[default]
     None,
     Ultrafast,
     Superfast,
@@ -56,13 +457,22 @@ pub enum Preset {
     Veryslow,
 }
 
-impl Default for Preset {
-    fn default() -> Self {
-        Preset::None
+impl Preset {
+    pub fn from_name(name: &str) -> Option<Preset> {
+        match name {
+            "ultrafast" => Some(Preset::Ultrafast),
+            "superfast" => Some(Preset::Superfast),
+            "veryfast" => Some(Preset::Veryfast),
+            "faster" => Some(Preset::Faster),
+            "fast" => Some(Preset::Fast),
+            "medium" => Some(Preset::Medium),
+            "slow" => Some(Preset::Slow),
+            "slower" => Some(Preset::Slower),
+            "veryslow" => Some(Preset::Veryslow),
+            _ => None,
+        }
     }
-}
 
-impl Preset {
     pub fn as_str(&self) -> Option<&'static str> {
         match self {
             Preset::None => None,
@@ -77,4 +487,66 @@ impl Preset {
             Preset::Veryslow => Some("veryslow"),
         }
     }
-}
\ No newline at end of file
+}
+
+// numbered still images encoded as video, e.g. a timelapse exported as frame_0001.png, frame_0002.png...
+#[derive(Clone)]
+pub struct ImageSequence {
+    // ffmpeg image2 pattern like frame_%04d.png
+    pub pattern: PathBuf,
+    pub first_frame: PathBuf,
+    pub start_number: u32,
+    pub frame_count: u32,
+    pub fps: u32,
+    // all frames together
+    pub size_bytes: u64,
+}
+
+impl ImageSequence {
+    pub fn duration(&self) -> f64 {
+        self.frame_count as f64 / self.fps.max(1) as f64
+    }
+
+    // stand-in source path for naming the output, the pattern's fixed part or else the folder name
+    pub fn name_path(&self) -> PathBuf {
+        let stem = self.first_frame.file_stem().unwrap_or_default().to_string_lossy();
+        let mut name = stem.trim_end_matches(|c: char| c.is_ascii_digit()).trim_end_matches(['_', '-', '.', ' ']).to_string();
+        if name.is_empty() {
+            name = self.first_frame.parent()
+                .and_then(|dir| dir.file_name())
+                .map_or("sequence".to_string(), |dir| dir.to_string_lossy().into_owned());
+        }
+        let extension = self.pattern.extension().unwrap_or_default().to_string_lossy();
+        self.first_frame.with_file_name(format!("{}.{}", name, extension))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crop_fits_inside_a_smaller_frame() {
+        let crop = Crop { width: 1920, height: 800, x: 0, y: 140 };
+        assert!(crop.fitted(1280, 720) == Crop { width: 1280, height: 720, x: 0, y: 0 });
+        assert!(crop.fitted(1920, 1080) == crop);
+    }
+
+    // only the offset overshoots, so the rectangle keeps its size and moves in
+    #[test]
+    fn crop_offset_is_moved_in() {
+        let crop = Crop { width: 640, height: 480, x: 1500, y: 700 };
+        assert!(crop.fitted(1920, 1080) == Crop { width: 640, height: 480, x: 1280, y: 600 });
+        assert_eq!(crop.to_filter(), "crop='min(iw,640)':'min(ih,480)':'min(1500,iw-ow)':'min(700,ih-oh)'");
+    }
+
+    // 5% under the limit, converted to the unit in use and rounded down so it never lands over
+    #[test]
+    fn service_target_size_converts_units() {
+        assert_eq!(ServiceTarget::Discord.target_size(SizeUnit::Binary), 9);
+        assert_eq!(ServiceTarget::Discord.target_size(SizeUnit::Decimal), 9);
+        assert_eq!(ServiceTarget::Gmail.target_size(SizeUnit::Decimal), 17);
+        assert_eq!(ServiceTarget::Gmail.target_size(SizeUnit::Binary), 16);
+        assert_eq!(ServiceTarget::Imgur.target_size(SizeUnit::Binary), 181);
+    }
+}
@@ -8,6 +8,86 @@ pub fn shell_quote(arg: &str) -> String {
     }
 }
 
+// escapes a filter option value for use inside a -filter:v graph. the value is quoted for the
+// option parser, then the quoted string is escaped again for the graph parser
+pub fn escape_filter_value(value: &str) -> String {
+    // backslashes are literal inside quotes, only a quote has to close, escape and reopen
+    let quoted = format!("'{}'", value.replace('\'', "'\\''"));
+    let mut escaped = String::with_capacity(quoted.len());
+    for c in quoted.chars() {
+        if matches!(c, '\\' | '\'' | '[' | ']' | ',' | ';') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// parses H:MM:SS, M:SS or plain seconds into seconds
+pub fn parse_timestamp(timestamp: &str) -> Option<f64> {
+    let mut seconds = 0.0;
+    for part in timestamp.trim().split(':') {
+        seconds = seconds * 60.0 + part.trim().parse::<f64>().ok()?;
+    }
+    Some(seconds)
+}
+
+// frame rate for ffmpeg's fps filter. NTSC rates become their exact fractions, 29.97 alone drifts
+// from 30000/1001 by a frame every few minutes
+pub fn frame_rate_arg(fps: f64) -> String {
+    for base in [24.0, 30.0, 48.0, 60.0, 120.0] {
+        if (fps - base * 1000.0 / 1001.0).abs() < 0.01 {
+            return format!("{}/1001", base as u32 * 1000);
+        }
+    }
+    format!("{:.3}", fps).trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+// logical cores on this machine, capped to what a cpu affinity mask can hold
+pub fn cpu_core_count() -> u32 {
+    std::thread::available_parallelism().map_or(1, |count| count.get() as u32).min(64)
+}
+
+// affinity mask for cores 0 to count - 1
+pub fn first_cores_mask(count: u32) -> u64 {
+    if count >= 64 { u64::MAX } else { (1 << count) - 1 }
+}
+
+// compares resolved paths so "./a.mp4" and "a.mp4" or a symlink and its target count as one file
+pub fn is_same_file(a: &std::path::Path, b: &std::path::Path) -> bool {
+    match (std::fs::canonicalize(a), std::fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => matches!((std::path::absolute(a), std::path::absolute(b)), (Ok(a), Ok(b)) if a == b),
+    }
+}
+
+pub fn format_duration(seconds: f64) -> String {
+    let total = seconds.max(0.0).round() as u64;
+    let (hours, minutes, secs) = (total / 3600, (total / 60) % 60, total % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, secs)
+    } else {
+        format!("{}:{:02}", minutes, secs)
+    }
+}
+
+// rough wait like "~14 min" or "~1 h 5 min"
+pub fn format_eta(seconds: f64) -> String {
+    let minutes = (seconds.max(0.0) / 60.0).round() as u64;
+    match minutes {
+        0 => "<1 min".to_string(),
+        1..60 => format!("~{} min", minutes),
+        _ => format!("~{} h {} min", minutes / 60, minutes % 60),
+    }
+}
+
+// drops characters that aren't allowed in file names on any of the supported platforms
+pub fn sanitize_file_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| !c.is_control() && !matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*'))
+        .collect()
+}
+
 pub fn format_size(bytes: u64) -> String {
     const KB: f64 = 1024.0;
     const MB: f64 = KB * 1024.0;
@@ -24,3 +104,173 @@ pub fn format_size(bytes: u64) -> String {
         format!("{:.2} GB", b / GB)
     }
 }
+
+// exact byte count with thousands separators, e.g. "10,485,760 B"
+pub fn format_bytes(bytes: u64) -> String {
+    let digits = bytes.to_string();
+    let mut grouped = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    format!("{} B", grouped)
+}
+
+pub fn format_bitrate(bits_per_second: u32) -> String {
+    if bits_per_second >= 10_000_000 {
+        format!("{:.1} Mbps", bits_per_second as f64 / 1_000_000.0)
+    } else {
+        format!("{} kbps", bits_per_second / 1000)
+    }
+}
+
+// parses "v1.2.3" or "1.2.3" into comparable parts, ignoring any pre-release suffix
+pub fn parse_version(version: &str) -> Option<(u32, u32, u32)> {
+    let version = version.trim().trim_start_matches('v');
+    let version = version.split(['-', '+']).next()?;
+    let mut parts = version.split('.').map(|p| p.parse::<u32>().ok());
+    let major = parts.next()??;
+    let minor = parts.next().flatten().unwrap_or(0);
+    let patch = parts.next().flatten().unwrap_or(0);
+    Some((major, minor, patch))
+}
+
+const COMPLETION_SOUND: &[u8] = include_bytes!("../assets/done.wav");
+
+// plays the bundled chime through the platform's own player without blocking. a missing player
+// only means no sound, so failures are ignored
+pub fn play_completion_sound() {
+    std::thread::spawn(|| {
+        let path = std::env::temp_dir().join("video-compressor-done.wav");
+        if std::fs::write(&path, COMPLETION_SOUND).is_err() {
+            return;
+        }
+        let players: Vec<(&str, Vec<String>)> = if cfg!(windows) {
+            // single quotes in a powershell string are escaped by doubling them
+            let script = format!("(New-Object Media.SoundPlayer '{}').PlaySync()", path.display().to_string().replace('\'', "''"));
+            vec![("powershell", vec!["-NoProfile".to_string(), "-Command".to_string(), script])]
+        } else if cfg!(target_os = "macos") {
+            vec![("afplay", vec![path.display().to_string()])]
+        } else {
+            // pulseaudio and pipewire first, plain alsa when neither is there
+            vec![("paplay", vec![path.display().to_string()]), ("aplay", vec!["-q".to_string(), path.display().to_string()])]
+        };
+        for (program, args) in players {
+            let mut command = std::process::Command::new(program);
+            command.args(args).stdout(std::process::Stdio::null()).stderr(std::process::Stdio::null());
+            // powershell would otherwise flash a console window over ours
+            #[cfg(windows)]
+            {
+                use std::os::windows::process::CommandExt;
+                const CREATE_NO_WINDOW: u32 = 0x08000000;
+                command.creation_flags(CREATE_NO_WINDOW);
+            }
+            if command.status().is_ok_and(|status| status.success()) {
+                return;
+            }
+        }
+    });
+}
+
+// powers the machine off through the platform's own command
+pub fn shut_down_computer() -> Result<(), String> {
+    let mut command = if cfg!(windows) {
+        let mut command = std::process::Command::new("shutdown");
+        command.args(["/s", "/t", "0"]);
+        command
+    } else if cfg!(target_os = "macos") {
+        let mut command = std::process::Command::new("osascript");
+        command.args(["-e", "tell application \"System Events\" to shut down"]);
+        command
+    } else {
+        let mut command = std::process::Command::new("systemctl");
+        command.arg("poweroff");
+        command
+    };
+    match command.status() {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(format!("the shutdown command exited with {}", status)),
+        Err(err) => Err(format!("could not run the shutdown command ({})", err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_version_reads_release_tags() {
+        assert_eq!(parse_version("v1.2.3"), Some((1, 2, 3)));
+        assert_eq!(parse_version(" 2.0 "), Some((2, 0, 0)));
+        assert_eq!(parse_version("v1.4.0-beta.2"), Some((1, 4, 0)));
+        assert_eq!(parse_version("nightly"), None);
+        assert!(parse_version("v1.10.0") > parse_version("v1.9.9"));
+    }
+
+    #[test]
+    fn parse_timestamp_reads_clock_and_seconds() {
+        assert_eq!(parse_timestamp("1:02:03"), Some(3723.0));
+        assert_eq!(parse_timestamp("2:30.5"), Some(150.5));
+        assert_eq!(parse_timestamp(" 45 "), Some(45.0));
+        assert_eq!(parse_timestamp("1:xx"), None);
+        assert_eq!(parse_timestamp(""), None);
+    }
+
+    #[test]
+    fn format_duration_pads_minutes_and_seconds() {
+        assert_eq!(format_duration(59.6), "1:00");
+        assert_eq!(format_duration(3723.0), "1:02:03");
+        assert_eq!(format_duration(-5.0), "0:00");
+    }
+
+    #[test]
+    fn sanitize_file_name_drops_reserved_characters() {
+        assert_eq!(sanitize_file_name("a<b>c:d\"e/f\\g|h?i*j"), "abcdefghij");
+        assert_eq!(sanitize_file_name("tab\there"), "tabhere");
+        assert_eq!(sanitize_file_name("_small (1)"), "_small (1)");
+    }
+
+    // quoted for the option parser, then every quote, backslash and graph separator escaped again
+    #[test]
+    fn escape_filter_value_survives_both_parsers() {
+        assert_eq!(escape_filter_value("a.srt"), r"\'a.srt\'");
+        assert_eq!(escape_filter_value("it's.srt"), r"\'it\'\\\'\'s.srt\'");
+        assert_eq!(escape_filter_value(r"C:\subs\[1], a;b.srt"), r"\'C:\\subs\\\[1\]\, a\;b.srt\'");
+    }
+
+    #[test]
+    fn frame_rate_arg_uses_exact_ntsc_fractions() {
+        assert_eq!(frame_rate_arg(29.97), "30000/1001");
+        assert_eq!(frame_rate_arg(23.976), "24000/1001");
+        assert_eq!(frame_rate_arg(59.94), "60000/1001");
+        assert_eq!(frame_rate_arg(30.0), "30");
+        assert_eq!(frame_rate_arg(12.5), "12.5");
+    }
+
+    #[test]
+    fn format_bytes_groups_thousands() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(999), "999 B");
+        assert_eq!(format_bytes(1000), "1,000 B");
+        assert_eq!(format_bytes(10_485_760), "10,485,760 B");
+    }
+
+    #[test]
+    fn first_cores_mask_covers_the_count() {
+        assert_eq!(first_cores_mask(0), 0);
+        assert_eq!(first_cores_mask(1), 0b1);
+        assert_eq!(first_cores_mask(4), 0b1111);
+        assert_eq!(first_cores_mask(63), u64::MAX >> 1);
+        assert_eq!(first_cores_mask(64), u64::MAX);
+        assert_eq!(first_cores_mask(100), u64::MAX);
+    }
+
+    #[test]
+    fn format_bitrate_switches_to_mbps_at_ten() {
+        assert_eq!(format_bitrate(128_000), "128 kbps");
+        assert_eq!(format_bitrate(9_999_999), "9999 kbps");
+        assert_eq!(format_bitrate(12_345_678), "12.3 Mbps");
+    }
+}
@@ -0,0 +1,40 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// a scratch folder per test, also used as home so the saved settings of the machine aren't read or written
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("video_compressor_gui_test_{}_{}", std::process::id(), name));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn run_cli(home: &Path, args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_video-compressor-gui"))
+        .args(args)
+        .env("HOME", home)
+        .env("XDG_CONFIG_HOME", home.join(".config"))
+        .env("APPDATA", home)
+        .output()
+        .unwrap()
+}
+
+// a script has to be able to tell from the exit code alone that nothing was compressed
+#[test]
+fn bad_input_exits_with_failure() {
+    let dir = scratch_dir("bad_input");
+    let input = dir.join("not_a_video.mp4");
+    std::fs::write(&input, b"this is not a video").unwrap();
+
+    let output = run_cli(&dir, &["--cli", input.to_str().unwrap()]);
+    std::fs::remove_dir_all(&dir).ok();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not_a_video.mp4: failed"));
+}
+
+#[test]
+fn missing_input_exits_with_failure() {
+    let dir = scratch_dir("missing_input");
+    let output = run_cli(&dir, &["--cli", dir.join("missing.mp4").to_str().unwrap()]);
+    std::fs::remove_dir_all(&dir).ok();
+    assert!(!output.status.success());
+}